@@ -0,0 +1,41 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use wgpu_bencher::{
+    kernels::layernorm::{ground_truth, LayerNorm, VARIANTS},
+    Comparison, GPUHandle, KernelBench, WgpuTimer,
+};
+
+lazy_static::lazy_static! {
+    pub static ref TIMER: WgpuTimer = WgpuTimer::new(pollster::block_on(async {
+        GPUHandle::new().await.unwrap()
+    }));
+}
+
+const PROB_M: usize = 2048;
+const PROB_N: usize = 512;
+
+fn benchmark(c: &mut Criterion<&WgpuTimer>) {
+    let eps = 1e-5;
+    let variant =
+        |template, workgroup_size| LayerNorm::new(template, workgroup_size, PROB_M, PROB_N, eps);
+    let (_, template, workgroup_size) = VARIANTS[0];
+    let tensors = variant(template, workgroup_size).tensors();
+
+    let comparison = VARIANTS.iter().fold(
+        Comparison::new("LayerNorm", tensors),
+        |comparison, &(name, template, workgroup_size)| {
+            comparison.variant(name, variant(template, workgroup_size))
+        },
+    );
+    let comparison = comparison
+        .baseline("Naive")
+        .reference(3, 1e-5, 1e-5, ground_truth);
+    let rows = wgpu_bencher::benchmark_comparison(c, &TIMER, comparison);
+    println!("LayerNorm\n{}", wgpu_bencher::ComparisonRow::table(&rows));
+}
+
+criterion_group!(
+    name = bench;
+    config = Criterion::default().with_measurement(&*TIMER);
+    targets = benchmark
+);
+criterion_main!(bench);
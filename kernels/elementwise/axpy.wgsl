@@ -0,0 +1,31 @@
+//Y = alpha * A + B, four elements per invocation when N is a multiple of 4.
+{% if VECTORIZED %}
+alias T = vec4<f32>;
+{% else %}
+alias T = f32;
+{% endif %}
+
+@group(0) @binding(0)
+var<storage, read> A: array<T>;
+
+@group(0) @binding(1)
+var<storage, read> B: array<T>;
+
+@group(0) @binding(2)
+var<storage, read_write> Y: array<T>;
+
+struct Meta {
+    numel: u32, //Number of Ts
+    alpha: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> metadata: Meta;
+
+@compute @workgroup_size({{ workgroup_size_x }}, {{ workgroup_size_y }}, {{ workgroup_size_z }})
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index < metadata.numel) {
+        Y[index] = metadata.alpha * A[index] + B[index];
+    }
+}
@@ -0,0 +1,4 @@
+//Does nothing, timing it measures the overhead of a dispatch with this geometry.
+@compute @workgroup_size({{ workgroup_size_x }}, {{ workgroup_size_y }}, {{ workgroup_size_z }})
+fn main() {
+}
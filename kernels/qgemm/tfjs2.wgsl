@@ -60,7 +60,7 @@ fn mm_write(batch: i32, row: i32, col: i32, valueIn: vec4<f32>) {
         let coords = vec3<i32>(batch, row, col);
         setOutputAtCoords(coords[0], coords[1], coords[2], value);
 {% else %}
-    if (row < metadata.dimAOuter && col < metadata.dimBOuter) {
+    if (row < metadata.outShape.y && col < metadata.outShape.z) {
         var value = valueIn;
         let coords = vec3<i32>(batch, row, col);
         setOutputAtCoords(coords[0], coords[1], coords[2], valueIn);
@@ -0,0 +1,37 @@
+//Chains of independent FMAs, measuring peak FP32 throughput.
+//Every invocation performs iterations * 4 (chains) * 4 (lanes) * 2 FLOPs.
+@group(0) @binding(0)
+var<storage, read> X: array<vec4<f32>>;
+
+@group(0) @binding(1)
+var<storage, read_write> Y: array<vec4<f32>>;
+
+struct Meta {
+    numel: u32, //Number of vec4s
+    iterations: u32,
+}
+
+@group(1) @binding(0)
+var<uniform> metadata: Meta;
+
+@compute @workgroup_size({{ workgroup_size_x }}, {{ workgroup_size_y }}, {{ workgroup_size_z }})
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= metadata.numel) {
+        return;
+    }
+    var a = X[index];
+    var b = a + 1.0;
+    var c = a + 2.0;
+    var d = a + 3.0;
+    let m = vec4<f32>(0.999);
+    let k = vec4<f32>(0.001);
+    for (var i = 0u; i < metadata.iterations; i++) {
+        a = fma(a, m, k);
+        b = fma(b, m, k);
+        c = fma(c, m, k);
+        d = fma(d, m, k);
+    }
+    //Written out so the chains cannot be eliminated
+    Y[index] = a + b + c + d;
+}
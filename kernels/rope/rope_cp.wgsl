@@ -12,6 +12,7 @@ struct Meta {
     offset: u32,
     base: f32,
     rotary_dim: u32,
+    head_dim: u32,
 }
 
 @group(1) @binding(0)
@@ -26,31 +27,27 @@ fn main(
         @builtin(num_workgroups) groups: vec3<u32>,
         @builtin(workgroup_id) group_id: vec3<u32>,
 ) {
-    let tid = local_id.x;
     let batch_idx = group_id.x;
     let head_idx = group_id.y;
     let tok_idx = group_id.z;
-    let half_rotary_dim = metadata.rotary_dim / 2u;
-
-    let hx = tid / half_rotary_dim;
-    let hy = tid % half_rotary_dim;
-    let rot_sign = select(-1.0, 1.0, hx == 0u); 
-
-    let global_offset = dot(vec4<u32>(batch_idx, head_idx, tok_idx, 1), metadata.in_strides) + tid;
-    let global_offset_rot = dot(vec4<u32>(batch_idx, head_idx, tok_idx, 1), metadata.in_strides) + (1u-hx) * half_rotary_dim + hy;
-
-    let x = X[global_offset];
-
-    let x_rot = rot_sign * X[global_offset_rot];
-
-    let ar = f32(hy) * 2.0;
-    let inv_freq = f32(tok_idx + metadata.offset) * (1.0 / pow(metadata.base, ar / f32(metadata.rotary_dim)));
-
-    let sin = sin(inv_freq);
-    let cos = cos(inv_freq);
-
-    workgroupBarrier();
-    Y[global_offset] = x * cos + x_rot * sin;
+    let row = vec4<u32>(batch_idx, head_idx, tok_idx, 0u);
+    let in_row = dot(row, metadata.in_strides);
+    let out_row = dot(row, metadata.out_strides);
+    let position = f32(tok_idx + metadata.offset);
+
+    //Pairs are interleaved, x[2i] rotates with x[2i + 1] as in rotary_embedding_torch
+    for (var i = local_id.x; i < metadata.head_dim; i += {{ workgroup_size_x }}u) {
+        let x = X[in_row + i * metadata.in_strides.w];
+        if (i >= metadata.rotary_dim) {
+            Y[out_row + i * metadata.out_strides.w] = x;
+            continue;
+        }
+        let pair = i / 2u;
+        let is_first = i % 2u == 0u;
+        let partner = select(i - 1u, i + 1u, is_first);
+        let x_rot = select(1.0, -1.0, is_first) * X[in_row + partner * metadata.in_strides.w];
+
+        let theta = position * pow(metadata.base, -2.0 * f32(pair) / f32(metadata.rotary_dim));
+        Y[out_row + i * metadata.out_strides.w] = x * cos(theta) + x_rot * sin(theta);
+    }
 }
-
-
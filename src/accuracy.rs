@@ -0,0 +1,383 @@
+use std::fmt::Display;
+
+use tabled::{builder::Builder, settings::Style};
+
+use crate::{CPUTensor, DType};
+
+//Upper edges of the absolute error histogram, between the exact and the >= 0.1 buckets
+const EDGES: [f64; 7] = [1e-7, 1e-6, 1e-5, 1e-4, 1e-3, 1e-2, 1e-1];
+const BAR_WIDTH: usize = 40;
+
+//Sign-magnitude float bits as a position on the number line, so that adjacent floats differ by 1
+fn ordered(bits: u64, width: u32) -> i64 {
+    let sign = 1u64 << (width - 1);
+    let magnitude = (bits & (sign - 1)) as i64;
+    if bits & sign != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+//Each element as f64, with its position on the dtype's number line for ULP distances
+fn elements(tensor: &CPUTensor) -> anyhow::Result<Vec<(f64, i64)>> {
+    Ok(match tensor.dt() {
+        DType::F32 => tensor
+            .to_vec::<f32>()?
+            .into_iter()
+            .map(|x| (x as f64, ordered(x.to_bits() as u64, 32)))
+            .collect(),
+        DType::F16 => tensor
+            .to_vec::<half::f16>()?
+            .into_iter()
+            .map(|x| (x.to_f64(), ordered(x.to_bits() as u64, 16)))
+            .collect(),
+        DType::BF16 => tensor
+            .to_vec::<half::bf16>()?
+            .into_iter()
+            .map(|x| (x.to_f64(), ordered(x.to_bits() as u64, 16)))
+            .collect(),
+        DType::Q8 => tensor
+            .to_vec::<i8>()?
+            .into_iter()
+            .map(|x| (x as f64, x as i64))
+            .collect(),
+        DType::I32 => tensor
+            .to_vec::<i32>()?
+            .into_iter()
+            .map(|x| (x as f64, x as i64))
+            .collect(),
+        DType::U32 => tensor
+            .to_vec::<u32>()?
+            .into_iter()
+            .map(|x| (x as f64, x as i64))
+            .collect(),
+        DType::WQ8 => anyhow::bail!(
+            "WQ8 packs weights and scales into one buffer, dequantize it before comparing"
+        ),
+    })
+}
+
+//C order index of the `flat`th element
+fn unravel(mut flat: usize, dims: &[usize]) -> Vec<usize> {
+    let mut index = vec![0; dims.len()];
+    for (i, &dim) in dims.iter().enumerate().rev() {
+        index[i] = flat % dim.max(1);
+        flat /= dim.max(1);
+    }
+    index
+}
+
+/// An element that is not close, with the value on either side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub index: Vec<usize>,
+    pub a: f64,
+    pub b: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NonFinite {
+    pub nan: usize,
+    pub inf: usize,
+}
+
+impl NonFinite {
+    fn count(&mut self, x: f64) {
+        self.nan += x.is_nan() as usize;
+        self.inf += x.is_infinite() as usize;
+    }
+}
+
+/// # Error Report
+///
+/// How far a tensor `a` is from `b`, elementwise as `numpy.isclose(a, b, rtol, atol)`.
+/// Errors are measured over the elements finite on both sides, which are counted apart.
+/// Relative errors are relative to `b`, and ULP distances are in the dtype of the tensors.
+#[derive(Debug, Clone)]
+pub struct ErrorReport {
+    pub atol: f32,
+    pub rtol: f32,
+    pub numel: usize,
+    pub mismatches: usize,
+    pub max_abs: f64,
+    pub max_abs_at: Vec<usize>,
+    pub max_rel: f64,
+    pub max_rel_at: Vec<usize>,
+    pub max_ulp: u64,
+    pub max_ulp_at: Vec<usize>,
+    pub mean_abs: f64,
+    pub rmse: f64,
+    /// 1 when both sides are zero.
+    pub cosine: f64,
+    /// Absolute errors that are exactly 0, below each of [`ErrorReport::EDGES`] in turn, then >= 0.1.
+    pub histogram: Vec<usize>,
+    /// The first mismatches in C order.
+    pub first: Vec<Mismatch>,
+    pub a_non_finite: NonFinite,
+    pub b_non_finite: NonFinite,
+}
+
+impl ErrorReport {
+    pub const EDGES: [f64; 7] = EDGES;
+    /// Mismatches kept by [`CPUTensor::error_report`].
+    pub const SHOWN: usize = 10;
+
+    pub fn new(
+        a: &CPUTensor,
+        b: &CPUTensor,
+        atol: f32,
+        rtol: f32,
+        shown: usize,
+    ) -> anyhow::Result<Self> {
+        if a.shape() != b.shape() {
+            anyhow::bail!("Shape mismatch {:?} != {:?}", a.shape(), b.shape())
+        }
+        if a.dt() != b.dt() {
+            anyhow::bail!("DType mismatch {:?} != {:?}", a.dt(), b.dt())
+        }
+        let dims = a.shape().to_vec();
+        let mut report = Self {
+            atol,
+            rtol,
+            numel: a.shape().numel(),
+            mismatches: 0,
+            max_abs: 0.0,
+            max_abs_at: vec![],
+            max_rel: 0.0,
+            max_rel_at: vec![],
+            max_ulp: 0,
+            max_ulp_at: vec![],
+            mean_abs: 0.0,
+            rmse: 0.0,
+            cosine: 1.0,
+            histogram: vec![0; EDGES.len() + 2],
+            first: vec![],
+            a_non_finite: NonFinite::default(),
+            b_non_finite: NonFinite::default(),
+        };
+        let (mut finite, mut total, mut squares) = (0usize, 0f64, 0f64);
+        let (mut dot, mut a_norm, mut b_norm) = (0f64, 0f64, 0f64);
+        for (i, ((a, a_ord), (b, b_ord))) in elements(a)?.into_iter().zip(elements(b)?).enumerate()
+        {
+            report.a_non_finite.count(a);
+            report.b_non_finite.count(b);
+            let abs = (a - b).abs();
+            let close = (a.is_nan() && b.is_nan())
+                || (a.is_infinite() && b.is_infinite() && a.signum() == b.signum())
+                || abs <= atol as f64 + rtol as f64 * b.abs();
+            if !close {
+                report.mismatches += 1;
+                if report.first.len() < shown {
+                    let index = unravel(i, &dims);
+                    report.first.push(Mismatch { index, a, b });
+                }
+            }
+            if !(a.is_finite() && b.is_finite()) {
+                continue;
+            }
+
+            finite += 1;
+            total += abs;
+            squares += abs * abs;
+            (dot, a_norm, b_norm) = (dot + a * b, a_norm + a * a, b_norm + b * b);
+            if abs > report.max_abs || report.max_abs_at.is_empty() {
+                report.max_abs = abs;
+                report.max_abs_at = unravel(i, &dims);
+            }
+            let rel = if abs == 0.0 {
+                0.0
+            } else if b == 0.0 {
+                f64::INFINITY
+            } else {
+                abs / b.abs()
+            };
+            if rel > report.max_rel || report.max_rel_at.is_empty() {
+                report.max_rel = rel;
+                report.max_rel_at = unravel(i, &dims);
+            }
+            let ulp = a_ord.abs_diff(b_ord);
+            if ulp > report.max_ulp || report.max_ulp_at.is_empty() {
+                report.max_ulp = ulp;
+                report.max_ulp_at = unravel(i, &dims);
+            }
+            let bucket = match abs == 0.0 {
+                true => 0,
+                false => 1 + EDGES.iter().take_while(|&&edge| abs >= edge).count(),
+            };
+            report.histogram[bucket] += 1;
+        }
+
+        if finite > 0 {
+            report.mean_abs = total / finite as f64;
+            report.rmse = (squares / finite as f64).sqrt();
+        }
+        report.cosine = match (a_norm, b_norm) {
+            (a_norm, b_norm) if a_norm == 0.0 && b_norm == 0.0 => 1.0,
+            (a_norm, b_norm) if a_norm == 0.0 || b_norm == 0.0 => 0.0,
+            (a_norm, b_norm) => dot / (a_norm.sqrt() * b_norm.sqrt()),
+        };
+        Ok(report)
+    }
+
+    pub fn is_close(&self) -> bool {
+        self.mismatches == 0
+    }
+
+    /// The report on one line.
+    pub fn summary(&self) -> String {
+        format!(
+            "AVGE={:.3e} MAE={:.3e} at {:?} RMSE={:.3e} max ULP={} cosine={:.9}",
+            self.mean_abs, self.max_abs, self.max_abs_at, self.rmse, self.max_ulp, self.cosine
+        )
+    }
+
+    fn bucket_label(bucket: usize) -> String {
+        match bucket {
+            0 => "0".to_string(),
+            b if b <= EDGES.len() => format!("< {:e}", EDGES[b - 1]),
+            _ => format!(">= {:e}", EDGES[EDGES.len() - 1]),
+        }
+    }
+}
+
+impl Display for ErrorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.mismatches {
+            0 => writeln!(f, "All {} samples close", self.numel)?,
+            n => writeln!(f, "{} of {} samples not close", n, self.numel)?,
+        }
+        writeln!(f, "  atol={} rtol={}", self.atol, self.rtol)?;
+        writeln!(
+            f,
+            "  max abs error:  {:.3e} at {:?}",
+            self.max_abs, self.max_abs_at
+        )?;
+        writeln!(
+            f,
+            "  max rel error:  {:.3e} at {:?}",
+            self.max_rel, self.max_rel_at
+        )?;
+        writeln!(
+            f,
+            "  max ULP:        {} at {:?}",
+            self.max_ulp, self.max_ulp_at
+        )?;
+        writeln!(f, "  mean abs error: {:.3e}", self.mean_abs)?;
+        writeln!(f, "  RMSE:           {:.3e}", self.rmse)?;
+        writeln!(f, "  cosine:         {:.9}", self.cosine)?;
+        writeln!(
+            f,
+            "  NaN/Inf:        a {}/{}, b {}/{}",
+            self.a_non_finite.nan,
+            self.a_non_finite.inf,
+            self.b_non_finite.nan,
+            self.b_non_finite.inf
+        )?;
+
+        writeln!(f, "Absolute error histogram")?;
+        let most = self
+            .histogram
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or_default()
+            .max(1);
+        for (bucket, &count) in self.histogram.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let bar = "#".repeat((count * BAR_WIDTH).div_ceil(most));
+            let label = Self::bucket_label(bucket);
+            writeln!(
+                f,
+                "  {:>9} | {:<width$} {}",
+                label,
+                bar,
+                count,
+                width = BAR_WIDTH
+            )?;
+        }
+
+        if !self.first.is_empty() {
+            writeln!(f, "First {} mismatches", self.first.len())?;
+            let mut builder = Builder::default();
+            builder.push_record(["Index", "a", "b", "Abs error"]);
+            for mismatch in &self.first {
+                builder.push_record([
+                    format!("{:?}", mismatch.index),
+                    format!("{}", mismatch.a),
+                    format!("{}", mismatch.b),
+                    format!("{:.3e}", (mismatch.a - mismatch.b).abs()),
+                ]);
+            }
+            write!(f, "{}", builder.build().with(Style::markdown()))?;
+        }
+        Ok(())
+    }
+}
+
+impl CPUTensor {
+    /// Compares the tensor (`a`) with `other` (`b`), keeping the first [`ErrorReport::SHOWN`] mismatches.
+    pub fn error_report(&self, other: &Self, atol: f32, rtol: f32) -> anyhow::Result<ErrorReport> {
+        ErrorReport::new(self, other, atol, rtol, ErrorReport::SHOWN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn error_report() {
+        let a = CPUTensor::from_slice(&[1f32, 2., 3., f32::NAN, 5., f32::INFINITY], shape![2, 3]);
+        let b = CPUTensor::from_slice(&[1f32, 2.5, 3.001, f32::NAN, 0., 6.], shape![2, 3]);
+        let report = a.error_report(&b, 1e-2, 0.0).unwrap();
+        assert!(!report.is_close());
+        //NaN matches NaN, so [0, 1], [1, 1] and the infinite [1, 2] are the mismatches
+        assert_eq!(report.mismatches, 3);
+        let indices = report
+            .first
+            .iter()
+            .map(|m| m.index.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(indices, [vec![0, 1], vec![1, 1], vec![1, 2]]);
+        assert_eq!((report.first[1].a, report.first[1].b), (5.0, 0.0));
+        assert_eq!(report.max_abs, 5.0);
+        assert_eq!(report.max_abs_at, [1, 1]);
+        assert_eq!(report.max_rel, f64::INFINITY);
+        assert_eq!(report.a_non_finite, NonFinite { nan: 1, inf: 1 });
+        assert_eq!(report.b_non_finite, NonFinite { nan: 1, inf: 0 });
+        //Errors of the four finite pairs: 0, 0.5, ~1e-3 and 5
+        assert_eq!(report.histogram.iter().sum::<usize>(), 4);
+        assert_eq!(
+            (
+                report.histogram[0],
+                report.histogram[5],
+                report.histogram[8]
+            ),
+            (1, 1, 2)
+        );
+        let rmse = ((0.25 + 25.0 + 1e-6) / 4f64).sqrt();
+        assert!((report.rmse - rmse).abs() < 1e-6);
+        let rendered = report.to_string();
+        assert!(rendered.starts_with("3 of 6 samples not close"));
+        assert!(rendered.contains("First 3 mismatches"));
+        assert!(a.all_close(&b, 1e-2, 0.0).is_err());
+
+        //Adjacent f16s are 1 ULP apart, and a rescaled vector keeps a cosine of 1
+        let one = half::f16::ONE;
+        let next = half::f16::from_bits(one.to_bits() + 1);
+        let a = CPUTensor::from_slice(&[one, one], shape![2]);
+        let b = CPUTensor::from_slice(&[next, one], shape![2]);
+        assert_eq!(a.error_report(&b, 0.0, 0.0).unwrap().max_ulp, 1);
+        let a = CPUTensor::from_slice(&[1f32, -2., 3.], shape![3]);
+        let b = CPUTensor::from_slice(&[2f32, -4., 6.], shape![3]);
+        assert!((a.error_report(&b, 0.0, 0.0).unwrap().cosine - 1.0).abs() < 1e-12);
+
+        let ids = CPUTensor::from_slice(&[1u32, 2, 3], shape![3]);
+        let report = ids.error_report(&ids, 0.0, 0.0).unwrap();
+        assert!(report.is_close() && report.first.is_empty());
+        assert!(ids.error_report(&b, 0.0, 0.0).is_err());
+    }
+}
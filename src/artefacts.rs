@@ -0,0 +1,182 @@
+use std::path::{Path, PathBuf};
+
+use rspirv::binary::Disassemble;
+use sha2::{Digest, Sha256};
+
+use crate::{output_dir, Backend, KernelSource, Params};
+
+//First 16 hex digits of the SHA-256 of `content`
+fn content_hash(content: &[u8]) -> String {
+    Sha256::digest(content)[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn disassemble(spirv: &[u8]) -> anyhow::Result<String> {
+    let words = spirv
+        .chunks_exact(4)
+        .map(|w| u32::from_ne_bytes([w[0], w[1], w[2], w[3]]))
+        .collect::<Vec<_>>();
+    let module = rspirv::dr::load_words(words)
+        .map_err(|e| anyhow::anyhow!("Failed to load SPIR-V: {:?}", e))?;
+    Ok(module.disassemble())
+}
+
+/// # Artefacts
+///
+/// What a benchmark actually ran, written to [`Artefacts::dir`]: the
+/// rendered WGSL, the Tera context it was rendered with and, if requested, its translations.
+/// Every file is named by the hash of the WGSL, so a changed shader gets new files and two
+/// runs can be diffed. `history` lists the hash of each distinct source in the order seen.
+#[derive(Debug, Clone)]
+pub struct Artefacts {
+    pub dir: PathBuf,
+    pub hash: String,
+    pub files: Vec<PathBuf>,
+}
+
+impl Artefacts {
+    /// Comma separated back-ends to translate to, e.g. `spv,metal`, or `all`.
+    pub const TRANSLATE_VAR: &'static str = "WGPU_BENCH_TRANSLATE";
+
+    /// `target/wgpu-bench/<kernel>/<params>/<template>-<context hash>/`, so that variants sharing
+    /// a kernel's name and params, e.g. autotune candidates, keep their own history.
+    pub fn dir(kernel: &str, params: &Params, source: &KernelSource) -> PathBuf {
+        let params = match params.is_empty() {
+            true => "default".to_string(),
+            false => params.to_string(),
+        };
+        let template = source
+            .template()
+            .and_then(|t| Path::new(t.path).file_stem()?.to_str())
+            .unwrap_or("wgsl");
+        let context = source
+            .context()
+            .map(|c| c.clone().into_json().to_string())
+            .unwrap_or_default();
+        let variant = format!("{}-{}", template, &content_hash(context.as_bytes())[..8]);
+        output_dir().join(kernel).join(params).join(variant)
+    }
+
+    /// The back-ends requested through `WGPU_BENCH_TRANSLATE`, by name or file extension.
+    pub fn translations() -> anyhow::Result<Vec<Backend>> {
+        let Ok(requested) = std::env::var(Self::TRANSLATE_VAR) else {
+            return Ok(vec![]);
+        };
+        if requested.trim() == "all" {
+            return Ok(Backend::ALL.to_vec());
+        }
+        requested
+            .split(',')
+            .filter(|name| !name.trim().is_empty())
+            .map(|name| {
+                let name = name.trim().to_lowercase();
+                Backend::ALL
+                    .into_iter()
+                    .find(|b| b.to_string().to_lowercase() == name || b.extension() == name)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("Unknown back-end {} in {}", name, Self::TRANSLATE_VAR)
+                    })
+            })
+            .collect()
+    }
+
+    pub fn write(
+        dir: &Path,
+        source: &KernelSource,
+        translations: &[Backend],
+    ) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let hash = content_hash(source.as_bytes());
+        let mut files = vec![];
+        let mut write = |extension: &str, content: &[u8]| -> anyhow::Result<()> {
+            let path = dir.join(format!("{}.{}", hash, extension));
+            std::fs::write(&path, content)?;
+            files.push(path);
+            Ok(())
+        };
+
+        write("wgsl", source.as_bytes())?;
+        if let Some(context) = source.context() {
+            let context = serde_json::to_string_pretty(&context.clone().into_json())?;
+            write("context.json", context.as_bytes())?;
+        }
+        if !translations.is_empty() {
+            let (module, info) = source.module()?;
+            for backend in translations {
+                let translated = backend.translate(&module, &info)?;
+                match backend {
+                    Backend::SpirV => write("spvasm", disassemble(&translated)?.as_bytes())?,
+                    _ => write(backend.extension(), &translated)?,
+                }
+            }
+        }
+
+        let history = dir.join("history");
+        let seen = std::fs::read_to_string(&history).unwrap_or_default();
+        if seen.lines().last() != Some(hash.as_str()) {
+            std::fs::write(&history, format!("{}{}\n", seen, hash))?;
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            hash,
+            files,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn rendered_artefacts() {
+        let dir = TempDir::new("artefacts");
+        let mut context = tera::Context::new();
+        context.insert("workgroup_size_x", &64);
+        context.insert("workgroup_size_y", &1);
+        context.insert("workgroup_size_z", &1);
+        let source = KernelSource::render(wgsl_template!("overhead/empty.wgsl"), &context);
+
+        let written = Artefacts::write(&dir, &source, &[Backend::SpirV, Backend::Msl]).unwrap();
+        let extensions = written
+            .files
+            .iter()
+            .map(|f| {
+                f.file_name()
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .replace(&written.hash, "")
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(extensions, [".wgsl", ".context.json", ".spvasm", ".metal"]);
+        let spvasm = std::fs::read_to_string(dir.join(format!("{}.spvasm", written.hash)));
+        assert!(spvasm.unwrap().contains("OpEntryPoint GLCompute"));
+
+        //Rewriting the same source keeps its name, a new one is added to the history
+        assert_eq!(
+            Artefacts::write(&dir, &source, &[]).unwrap().hash,
+            written.hash
+        );
+        context.insert("workgroup_size_x", &128);
+        let source = KernelSource::render(wgsl_template!("overhead/empty.wgsl"), &context);
+        let rewritten = Artefacts::write(&dir, &source, &[]).unwrap();
+        assert_ne!(rewritten.hash, written.hash);
+        let history = std::fs::read_to_string(dir.join("history")).unwrap();
+        assert_eq!(history, format!("{}\n{}\n", written.hash, rewritten.hash));
+
+        //Variants of a kernel are kept apart by their template and context
+        let params = params![N = 1];
+        let variant = Artefacts::dir("Kernel", &params, &source);
+        assert!(variant.starts_with(output_dir().join("Kernel").join("N=1")));
+        let name = variant.file_name().unwrap().to_string_lossy();
+        assert!(name.starts_with("empty-"));
+        let rerendered = KernelSource::render(wgsl_template!("overhead/empty.wgsl"), &context);
+        assert_eq!(Artefacts::dir("Kernel", &params, &rerendered), variant);
+        context.insert("workgroup_size_x", &64);
+        let other = KernelSource::render(wgsl_template!("overhead/empty.wgsl"), &context);
+        assert_ne!(Artefacts::dir("Kernel", &params, &other), variant);
+    }
+}
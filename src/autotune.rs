@@ -0,0 +1,226 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use crate::{measure, output_dir, percentile, KernelBench, Params, WgpuTimer};
+
+type Constraint = Box<dyn Fn(&Params) -> bool>;
+
+/// # SearchSpace
+///
+/// The configurations explored by the [`Autotuner`]: the cartesian product of every axis,
+/// minus those rejected by a constraint.
+#[derive(Default)]
+pub struct SearchSpace {
+    axes: Vec<(String, Vec<String>)>,
+    constraints: Vec<Constraint>,
+}
+
+impl SearchSpace {
+    pub fn axis<T: Display>(
+        mut self,
+        key: impl Into<String>,
+        values: impl IntoIterator<Item = T>,
+    ) -> Self {
+        let values = values.into_iter().map(|v| v.to_string()).collect();
+        self.axes.push((key.into(), values));
+        self
+    }
+
+    /// Rejects configurations for which `valid` returns false, e.g. tiles that don't divide the problem.
+    /// Configurations are checked together with the problem parameters.
+    pub fn constraint(mut self, valid: impl Fn(&Params) -> bool + 'static) -> Self {
+        self.constraints.push(Box::new(valid));
+        self
+    }
+
+    /// Every configuration in the space, without checking constraints.
+    pub fn configs(&self) -> Vec<Params> {
+        self.axes
+            .iter()
+            .fold(vec![Params::default()], |configs, (key, values)| {
+                configs
+                    .iter()
+                    .flat_map(|config| values.iter().map(|v| config.clone().with(key, v)))
+                    .collect()
+            })
+    }
+
+    /// Whether `params` satisfies every constraint.
+    pub fn allows(&self, params: &Params) -> bool {
+        self.constraints.iter().all(|valid| valid(params))
+    }
+}
+
+/// The fastest configuration found for a problem.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, derive_new::new)]
+pub struct Tuned {
+    pub config: Params,
+    pub ns: f64, //Median nanoseconds per dispatch
+}
+
+/// # Autotuner
+///
+/// Searches a [`SearchSpace`] for the fastest correct configuration of a kernel.
+/// Winners are persisted per adapter, so later runs on the same device reuse them.
+pub struct Autotuner<'a> {
+    timer: &'a WgpuTimer,
+    samples: usize,
+    cache: PathBuf,
+}
+
+impl<'a> Autotuner<'a> {
+    pub const SAMPLES: usize = 10;
+
+    pub fn new(timer: &'a WgpuTimer) -> Self {
+        let file = format!("{}.json", timer.handle().fingerprint());
+        Self {
+            timer,
+            samples: Self::SAMPLES,
+            cache: output_dir().join("autotune").join(file),
+        }
+    }
+
+    /// Number of timed samples per candidate.
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// File holding the winners for this adapter.
+    pub fn cache_path(&self) -> &Path {
+        &self.cache
+    }
+
+    /// Returns the persisted winner for `problem`, or searches the space and persists it.
+    /// `kernel` builds a kernel from the problem parameters merged with a configuration.
+    pub fn tune<K: KernelBench>(
+        &self,
+        problem: &Params,
+        space: &SearchSpace,
+        kernel: impl Fn(&Params) -> K,
+    ) -> anyhow::Result<Tuned> {
+        let key = format!("{}/{}", K::name(), problem);
+        let mut winners = self.load()?;
+        if let Some(tuned) = winners.get(&key) {
+            log::info!("Reusing tuned configuration for {}: {}", key, tuned.config);
+            return Ok(tuned.clone());
+        }
+        let tuned = self.search(problem, space, kernel)?;
+        log::info!(
+            "{}: best configuration {} ({:.4} ns)",
+            key,
+            tuned.config,
+            tuned.ns
+        );
+        winners.insert(key, tuned.clone());
+        self.save(&winners)?;
+        Ok(tuned)
+    }
+
+    /// Validates and times every allowed configuration, ignoring persisted winners.
+    /// Configurations exceeding the device limits or failing validation are skipped.
+    pub fn search<K: KernelBench>(
+        &self,
+        problem: &Params,
+        space: &SearchSpace,
+        kernel: impl Fn(&Params) -> K,
+    ) -> anyhow::Result<Tuned> {
+        let configs = space.configs();
+        let mut best: Option<Tuned> = None;
+        for config in configs.iter() {
+            let params = problem.merge(config);
+            if !space.allows(&params) {
+                log::debug!("{}: {} rejected by constraint", K::name(), config);
+                continue;
+            }
+            match self.evaluate(&kernel(&params)) {
+                Ok(ns) => {
+                    log::info!("{}: {} took {:.4} ns", K::name(), config, ns);
+                    if best.as_ref().map_or(true, |b| ns < b.ns) {
+                        best = Some(Tuned::new(config.clone(), ns));
+                    }
+                }
+                Err(e) => log::info!("{}: skipping {}: {:#}", K::name(), config, e),
+            }
+        }
+        best.ok_or_else(|| {
+            anyhow::anyhow!(
+                "No valid configuration of {} for {} among {} candidates",
+                K::name(),
+                problem,
+                configs.len()
+            )
+        })
+    }
+
+    //Median nanoseconds per dispatch, if the kernel fits the device and validates
+    fn evaluate<K: KernelBench>(&self, kernel: &K) -> anyhow::Result<f64> {
+        let device = self.timer.handle().device();
+        let tensors = kernel.tensors();
+        kernel.workload(&tensors).check_limits(&device.limits())?;
+
+        //Capture shader and pipeline errors instead of panicking
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let validation = kernel.validate(self.timer.handle(), &tensors);
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            anyhow::bail!("{}", error);
+        }
+        validation?;
+
+        let mut samples = measure(self.timer, kernel, self.samples);
+        samples.sort_by(f64::total_cmp);
+        Ok(percentile(&samples, 50.0))
+    }
+
+    fn load(&self) -> anyhow::Result<BTreeMap<String, Tuned>> {
+        match std::fs::read_to_string(&self.cache) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, winners: &BTreeMap<String, Tuned>) -> anyhow::Result<()> {
+        if let Some(dir) = self.cache.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.cache, serde_json::to_string_pretty(winners)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn search_space() {
+        let space = SearchSpace::default()
+            .axis("TILE_DIM", [16, 32, 64])
+            .axis("ROW_PER_THREAD", [4, 8])
+            .constraint(|p| {
+                p.get::<usize>("M").unwrap() % p.get::<usize>("TILE_DIM").unwrap() == 0
+            });
+        let configs = space.configs();
+        assert_eq!(configs.len(), 6);
+        assert_eq!(configs[1].to_string(), "TILE_DIM=16,ROW_PER_THREAD=8");
+
+        let problem = params![M = 96];
+        let allowed = configs
+            .iter()
+            .filter(|c| space.allows(&problem.merge(c)))
+            .count();
+        assert_eq!(allowed, 4);
+
+        let tuned = Tuned::new(configs[2].clone(), 1.5);
+        let json = serde_json::to_string(&tuned).unwrap();
+        assert_eq!(
+            json,
+            r#"{"config":"TILE_DIM=32,ROW_PER_THREAD=4","ns":1.5}"#
+        );
+        assert_eq!(serde_json::from_str::<Tuned>(&json).unwrap(), tuned);
+    }
+}
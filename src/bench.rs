@@ -1,8 +1,14 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, path::PathBuf, time::Duration};
 
-use criterion::{BenchmarkId, Criterion, Throughput};
+use criterion::{measurement::Measurement, BenchmarkGroup, BenchmarkId, Criterion, Throughput};
+use naga::valid::Capabilities;
 
-use crate::{CPUTensor, GPUBuffer, GPUHandle, GPUTensor, OpMetadata, WgpuTimer, Workload};
+use crate::{
+    check_bindings, check_metadata, percentile, save_results, wgsl_template, AdapterRecord,
+    Artefacts, BenchRecord, CPUTensor, Cost, DevicePeaks, GPUBuffer, GPUHandle, GPUTensor, Golden,
+    KernelSource, OpMetadata, Params, Roofline, RooflinePoint, ShaderBinding, TimingMode,
+    WgpuTimer, Workload, MAX_QUERIES,
+};
 
 pub trait KernelContextExt {
     fn insert_workload(&mut self, workload: &Workload);
@@ -16,14 +22,121 @@ impl KernelContextExt for tera::Context {
     }
 }
 
+/// Number of dispatches recorded in each timed sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dispatches {
+    Fixed(u32),
+    /// Calibrated before benchmarking so that each timed sample lasts roughly `target`.
+    Adaptive {
+        target: Duration,
+    },
+}
+
+impl Default for Dispatches {
+    fn default() -> Self {
+        Dispatches::Fixed(WgpuTimer::COMPUTE_PER_QUERY as _)
+    }
+}
+
 pub trait KernelBench: std::fmt::Debug {
     type Metadata: OpMetadata;
     fn name() -> &'static str;
-    fn source(&self, workload: &Workload) -> String;
+    /// Name of this instance in results and artefacts: [`KernelBench::name`], unless the
+    /// kernel is only known at runtime, e.g. one declared by a [`crate::Manifest`].
+    fn label(&self) -> String {
+        Self::name().to_string()
+    }
+    fn source(&self, workload: &Workload) -> KernelSource;
     fn tensors(&self) -> Vec<CPUTensor>;
     fn workload(&self, tensors: &[CPUTensor]) -> Workload;
     fn metadata(&self, tensors: &[CPUTensor]) -> Self::Metadata;
-    fn validate(&self, tensors: &[CPUTensor]);
+    /// Checks the kernel's output against a reference implementation.
+    fn validate(&self, handle: &GPUHandle, tensors: &[CPUTensor]) -> anyhow::Result<()>;
+    /// FLOPs performed and bytes moved by a single dispatch.
+    fn cost(&self, tensors: &[CPUTensor]) -> Cost;
+
+    fn dispatches(&self) -> Dispatches {
+        Dispatches::default()
+    }
+
+    /// Parameters identifying this instance, e.g. its problem size.
+    fn params(&self) -> Params {
+        Params::default()
+    }
+
+    /// Recomputes the [`Golden`] that [`KernelBench::validate`] replays for `seed`, for kernels
+    /// whose reference needs Python. Other kernels have nothing to record.
+    fn record_golden(&self, _seed: u64) -> anyhow::Result<Option<Golden>> {
+        Ok(None)
+    }
+}
+
+/// A kernel compiled and bound to its tensors, ready to be dispatched.
+pub struct PreparedKernel {
+    pub workload: Workload,
+    pub pipeline: wgpu::ComputePipeline,
+    pub bind_groups: Vec<wgpu::BindGroup>,
+    pub tensors: Vec<GPUTensor>,
+}
+
+impl PreparedKernel {
+    pub fn new<K: KernelBench>(handle: &GPUHandle, kernel: &K, tensors: &[CPUTensor]) -> Self {
+        let workload = kernel.workload(tensors);
+        log::debug!("Workload: {:?}", workload);
+        let source = kernel.source(&workload);
+        log::debug!("Source: {}", source);
+        write_artefacts(kernel, &source);
+        if let Err(e) = preflight::<K>(&source, tensors, handle.capabilities()) {
+            panic!("{} failed preflight: {:#}", kernel.label(), e);
+        }
+        let pipeline = source_to_pipeline(handle, &source);
+        let uniform_buffer = kernel.metadata(tensors).into_buffer(handle);
+        let gpu_tensors = tensors
+            .iter()
+            .cloned()
+            .map(|t| t.into_gpu(handle))
+            .collect::<Vec<_>>();
+        let bind_groups = tensors_to_bind_groups(handle, &gpu_tensors, uniform_buffer, &pipeline);
+        Self {
+            workload,
+            pipeline,
+            bind_groups,
+            tensors: gpu_tensors,
+        }
+    }
+
+    pub fn dispatch(&self, handle: &GPUHandle, timer: Option<&WgpuTimer>) {
+        dispatch(
+            handle,
+            &self.workload,
+            &self.bind_groups,
+            &self.pipeline,
+            timer,
+        );
+    }
+}
+
+//Failing to write artefacts shouldn't fail the benchmark
+fn write_artefacts<K: KernelBench>(kernel: &K, source: &KernelSource) {
+    let dir = Artefacts::dir(&kernel.label(), &kernel.params(), source);
+    match Artefacts::translations().and_then(|t| Artefacts::write(&dir, source, &t)) {
+        Ok(artefacts) => log::info!("{} source {}", kernel.label(), artefacts.hash),
+        Err(e) => log::warn!("Failed to write artefacts to {}: {:#}", dir.display(), e),
+    }
+}
+
+/// Validates the rendered source with naga, allowing only `capabilities`, and checks it against
+/// the kernel's tensors and metadata, so that a mistake is reported by name rather than as a
+/// wgpu panic or silently wrong results. Returns the shader's bindings.
+pub fn preflight<K: KernelBench>(
+    source: &KernelSource,
+    tensors: &[CPUTensor],
+    capabilities: Capabilities,
+) -> anyhow::Result<Vec<ShaderBinding>> {
+    let (module, info) = source.module_for(capabilities)?;
+    let bindings = check_bindings(&module, &info, tensors)?;
+    check_metadata::<K::Metadata>(&module, &info)?;
+    Ok(bindings)
 }
 
 pub fn dispatch_validate<K: KernelBench>(
@@ -32,51 +145,93 @@ pub fn dispatch_validate<K: KernelBench>(
     tensors: &[CPUTensor],
 ) -> Vec<GPUTensor> {
     let _ = env_logger::builder().is_test(true).try_init();
-    let workload = kernel.workload(&tensors);
-    log::debug!("Workload: {:?}", workload);
-    let source = kernel.source(&workload);
-    log::debug!("Source: {}", source);
-    let pipeline = source_to_pipeline(handle, &source);
-    let uniform_buffer = kernel.metadata(&tensors).into_buffer(handle);
-    let gpu_tensors = tensors
-        .into_iter()
-        .cloned()
-        .map(|t| t.into_gpu(handle))
-        .collect::<Vec<_>>();
-    let bind_groups = tensors_to_bind_groups(handle, &gpu_tensors, uniform_buffer, &pipeline);
-    dispatch(handle, &workload, &bind_groups, &pipeline, None);
-    gpu_tensors
+    let prepared = PreparedKernel::new(handle, kernel, tensors);
+    prepared.dispatch(handle, None);
+    prepared.tensors
+}
+
+fn bind<'a>(
+    cpass: &mut wgpu::ComputePass<'a>,
+    bind_groups: &'a [wgpu::BindGroup],
+    pipeline: &'a wgpu::ComputePipeline,
+) {
+    for (i, bind_group) in bind_groups.iter().enumerate() {
+        cpass.set_bind_group(i as _, bind_group, &[]);
+    }
+    cpass.set_pipeline(pipeline);
 }
 
+/// Dispatches the workload and waits for completion.
+/// If a timer is provided, it is dispatched `timer.dispatches()` times and timed according
+/// to the timer's [`TimingMode`], otherwise it is dispatched once.
 #[inline(always)]
 pub fn dispatch(
     handle: &GPUHandle,
     workload: &Workload,
     bind_groups: &[wgpu::BindGroup],
     pipeline: &wgpu::ComputePipeline,
-    timestamp_writes: Option<wgpu::ComputePassTimestampWrites>,
+    timer: Option<&WgpuTimer>,
 ) {
     let mut encoder = handle
         .device()
         .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-    {
-        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-            label: None,
-            timestamp_writes,
-        });
-        for (i, bind_group) in bind_groups.iter().enumerate() {
-            cpass.set_bind_group(i as _, bind_group, &[]);
+    let (x, y, z) = workload.count().as_tuple();
+    let dispatches = timer.map_or(1, WgpuTimer::dispatches);
+
+    if let Some(queries) = timer.and_then(WgpuTimer::per_dispatch_queries) {
+        let first = queries.reserve(dispatches);
+        if handle.supports_timestamps_inside_passes() {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: None,
+                timestamp_writes: None,
+            });
+            bind(&mut cpass, bind_groups, pipeline);
+            for i in 0..dispatches {
+                cpass.write_timestamp(queries.query_set(), first + 2 * i);
+                cpass.dispatch_workgroups(x, y, z);
+                cpass.write_timestamp(queries.query_set(), first + 2 * i + 1);
+            }
+        } else {
+            for i in 0..dispatches {
+                let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: None,
+                    timestamp_writes: Some(wgpu::ComputePassTimestampWrites {
+                        query_set: queries.query_set(),
+                        beginning_of_pass_write_index: Some(first + 2 * i),
+                        end_of_pass_write_index: Some(first + 2 * i + 1),
+                    }),
+                });
+                bind(&mut cpass, bind_groups, pipeline);
+                cpass.dispatch_workgroups(x, y, z);
+            }
+        }
+        handle.queue().submit(Some(encoder.finish()));
+        handle.device().poll(wgpu::Maintain::Wait);
+        queries.advance(dispatches);
+    } else {
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: None,
+                timestamp_writes: timer.and_then(WgpuTimer::timestamp_writes),
+            });
+            bind(&mut cpass, bind_groups, pipeline);
+            for _ in 0..dispatches {
+                cpass.dispatch_workgroups(x, y, z);
+            }
         }
-        cpass.set_pipeline(pipeline);
-        let (x, y, z) = workload.count().as_tuple();
-        for _ in 0..WgpuTimer::COMPUTE_PER_QUERY {
-            cpass.dispatch_workgroups(x, y, z);
+        handle.queue().submit(Some(encoder.finish()));
+        handle.device().poll(wgpu::Maintain::Wait);
+        if let Some(timer) = timer {
+            timer.increment_query();
         }
     }
-    handle.queue().submit(Some(encoder.finish()));
-    handle.device().poll(wgpu::Maintain::Wait);
+    if let Some(timer) = timer {
+        timer.record_iteration();
+    }
 }
 
+/// Creates the pipeline without wgpu's own shader validation, so `source` must already have
+/// been validated with the device's [`GPUHandle::capabilities`], e.g. through [`preflight`].
 pub fn source_to_pipeline(handle: &GPUHandle, source: &str) -> wgpu::ComputePipeline {
     let shader_module = unsafe {
         handle
@@ -137,34 +292,256 @@ pub fn tensors_to_bind_groups(
     standard_bind_groups
 }
 
-pub fn benchmark<K: KernelBench>(
-    c: &mut Criterion<&WgpuTimer>,
-    timer: &WgpuTimer,
-    kernel: K,
-    throughput: Throughput,
-) {
+/// Calibrates the number of dispatches so that a timed sample lasts roughly `target`.
+pub fn calibrate_dispatches(timer: &WgpuTimer, prepared: &PreparedKernel, target: Duration) -> u32 {
+    const CALIBRATION_ITERS: u32 = 10;
+    let handle = timer.handle();
+    timer.set_dispatches(1);
+    prepared.dispatch(handle, Some(timer)); //warmup
+
+    let start = timer.start();
+    for _ in 0..CALIBRATION_ITERS {
+        prepared.dispatch(handle, Some(timer));
+    }
+    let elapsed = timer.to_f64(&timer.end(start));
+    let per_dispatch = (elapsed / CALIBRATION_ITERS as f64).max(1.0);
+
+    let max_dispatches = match timer.mode() {
+        TimingMode::PerDispatch => MAX_QUERIES / 2,
+        TimingMode::Averaged => u32::MAX,
+    };
+    let dispatches =
+        ((target.as_nanos() as f64 / per_dispatch).round() as u32).clamp(1, max_dispatches);
+    log::info!(
+        "Calibrated {} dispatches of {:.4} ns for a {:?} sample",
+        dispatches,
+        per_dispatch,
+        target
+    );
+    dispatches
+}
+
+/// Sets the timer to the requested number of dispatches, calibrating if needed.
+fn apply_dispatches(timer: &WgpuTimer, dispatches: Dispatches, prepared: &PreparedKernel) -> u32 {
+    let dispatches = match dispatches {
+        Dispatches::Fixed(dispatches) => dispatches,
+        Dispatches::Adaptive { target } => calibrate_dispatches(timer, prepared, target),
+    };
+    timer.set_dispatches(dispatches);
+    dispatches
+}
+
+//Times `samples` measurements of `ITERS_PER_SAMPLE` iterations each
+fn collect_samples(timer: &WgpuTimer, samples: usize, iteration: impl Fn()) -> Vec<f64> {
+    const ITERS_PER_SAMPLE: usize = 10;
+    iteration(); //warmup
+    timer.reset_samples();
+    for _ in 0..samples {
+        let start = timer.start();
+        for _ in 0..ITERS_PER_SAMPLE {
+            iteration();
+        }
+        timer.end(start);
+    }
+    timer.samples()
+}
+
+/// Times a kernel outside of criterion.
+/// Returns the nanoseconds per dispatch of each of the `samples` measurements.
+pub fn measure<K: KernelBench>(timer: &WgpuTimer, kernel: &K, samples: usize) -> Vec<f64> {
     let handle = timer.handle();
     let tensors = kernel.tensors();
-    kernel.validate(&tensors);
-    let workload = kernel.workload(&tensors);
-    let source = kernel.source(&workload);
+    let prepared = PreparedKernel::new(handle, kernel, &tensors);
+    apply_dispatches(timer, kernel.dispatches(), &prepared);
+    apply_overhead(timer, &prepared.workload);
+    collect_samples(timer, samples, || prepared.dispatch(handle, Some(timer)))
+}
+
+/// Times an empty kernel with the geometry of `workload`, using the timer's current
+/// number of dispatches. Returns the median nanoseconds per dispatch, which is the pass
+/// and dispatch overhead included in every measurement of a kernel with this geometry.
+pub fn measure_overhead(timer: &WgpuTimer, workload: &Workload) -> f64 {
+    const SAMPLES: usize = 10;
+    let handle = timer.handle();
+    let mut context = tera::Context::new();
+    context.insert_workload(workload);
+    let source = KernelSource::render(wgsl_template!("overhead/empty.wgsl"), &context);
+    if let Err(e) = source.module_for(handle.capabilities()) {
+        panic!("Overhead kernel failed validation: {:#}", e);
+    }
     let pipeline = source_to_pipeline(handle, &source);
-    let uniform_buffer = kernel.metadata(&tensors).into_buffer(handle);
 
-    let gpu_tensors = tensors
-        .into_iter()
-        .map(|t| t.into_gpu(handle))
+    let overhead = timer.overhead();
+    timer.set_overhead(None);
+    let mut samples = collect_samples(timer, SAMPLES, || {
+        dispatch(handle, workload, &[], &pipeline, Some(timer))
+    });
+    timer.set_overhead(overhead);
+    samples.sort_by(f64::total_cmp);
+    percentile(&samples, 50.0)
+}
+
+//Measures the overhead for the kernel about to be timed, replacing that of the previous kernel
+fn apply_overhead(timer: &WgpuTimer, workload: &Workload) -> f64 {
+    timer.set_overhead(None);
+    let overhead = measure_overhead(timer, workload);
+    timer.set_overhead(Some(overhead));
+    overhead
+}
+
+/// Directory for everything the harness writes besides criterion's own reports.
+/// Defaults to `target/wgpu-bench`, respecting `CARGO_TARGET_DIR`.
+pub fn output_dir() -> PathBuf {
+    std::env::var_os("CARGO_TARGET_DIR")
+        .map_or_else(|| PathBuf::from("target"), PathBuf::from)
+        .join("wgpu-bench")
+}
+
+pub fn benchmark<K: KernelBench>(c: &mut Criterion<&WgpuTimer>, timer: &WgpuTimer, kernel: K) {
+    //Resolve peaks first, measuring them reconfigures the timer
+    let peaks = timer.peaks();
+    let mut group = c.benchmark_group(kernel.label());
+    let record = bench_point(&mut group, timer, &kernel, &kernel.params(), peaks);
+    group.finish();
+    save_results(&kernel.label(), &Vec::from_iter(record));
+}
+
+/// Benchmarks a kernel at every parameter point, e.g. a range of shapes or tile sizes.
+/// `kernel` builds the kernel for a point, and each point is registered as its own
+/// `BenchmarkId` with tensors, pipeline and throughput rebuilt for it.
+pub fn benchmark_sweep<K: KernelBench>(
+    c: &mut Criterion<&WgpuTimer>,
+    timer: &WgpuTimer,
+    points: &[Params],
+    kernel: impl Fn(&Params) -> K,
+) -> Vec<BenchRecord> {
+    let peaks = timer.peaks();
+    let kernels = points.iter().map(kernel).collect::<Vec<_>>();
+    let name = kernels.first().map_or(K::name().to_string(), K::label);
+    let mut group = c.benchmark_group(&name);
+    let records = kernels
+        .iter()
+        .zip(points)
+        .filter_map(|(kernel, params)| bench_point(&mut group, timer, kernel, params, peaks))
         .collect::<Vec<_>>();
-    let bind_groups = tensors_to_bind_groups(handle, &gpu_tensors, uniform_buffer, &pipeline);
+    group.finish();
+    save_results(&name, &records);
+    records
+}
+
+fn bench_point<K: KernelBench>(
+    group: &mut BenchmarkGroup<&WgpuTimer>,
+    timer: &WgpuTimer,
+    kernel: &K,
+    params: &Params,
+    peaks: Option<DevicePeaks>,
+) -> Option<BenchRecord> {
+    let name = kernel.label();
+    let label = point_label(&name, params);
+    let tensors = kernel.tensors();
+    if let Err(e) = kernel.validate(timer.handle(), &tensors) {
+        panic!("{} failed validation: {:?}", label, e);
+    }
+    let cost = kernel.cost(&tensors);
+    let prepared = PreparedKernel::new(timer.handle(), kernel, &tensors);
+    let id = BenchmarkId::new(&name, params);
+    bench_prepared(
+        group,
+        timer,
+        id,
+        &name,
+        params,
+        &prepared,
+        cost,
+        kernel.dispatches(),
+        peaks,
+    )
+}
+
+//e.g. SGEMMBenchmark/M=1024,N=1024,K=1024
+fn point_label(kernel: &str, params: &Params) -> String {
+    if params.is_empty() {
+        kernel.to_string()
+    } else {
+        format!("{}/{}", kernel, params)
+    }
+}
 
-    let mut group = c.benchmark_group(K::name());
-    group.throughput(throughput);
-    group.bench_function(BenchmarkId::new(K::name(), 0), |b| {
+/// Benchmarks a prepared kernel within `group`, reporting overhead, per-dispatch statistics
+/// and roofline placement. Returns the record of the `kernel` at `params`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn bench_prepared(
+    group: &mut BenchmarkGroup<&WgpuTimer>,
+    timer: &WgpuTimer,
+    id: BenchmarkId,
+    kernel: &str,
+    params: &Params,
+    prepared: &PreparedKernel,
+    cost: Cost,
+    dispatches: Dispatches,
+    peaks: Option<DevicePeaks>,
+) -> Option<BenchRecord> {
+    let handle = timer.handle();
+    let label = &point_label(kernel, params);
+    timer.set_overhead(None);
+    let dispatches = apply_dispatches(timer, dispatches, prepared);
+    let overhead = apply_overhead(timer, &prepared.workload);
+    timer.set_cost(cost);
+
+    log::info!(
+        "{}: timing with {} clock, {} dispatches per sample",
+        label,
+        timer.clock(),
+        dispatches
+    );
+    timer.reset_dispatch_stats();
+    timer.reset_samples();
+    group.throughput(Throughput::Bytes(cost.bytes));
+    group.bench_function(id, |b| {
         b.iter(|| {
-            let tsw = timer.timestamp_writes();
-            dispatch(handle, &workload, &bind_groups, &pipeline, Some(tsw));
-            timer.increment_query();
+            prepared.dispatch(handle, Some(timer));
         });
     });
-    group.finish()
+    if let Some(stats) = timer.dispatch_stats() {
+        log::info!("{} per-dispatch (raw): {}", label, stats);
+    }
+
+    let samples = timer.samples();
+    if samples.is_empty() {
+        return None;
+    }
+    let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+    let raw = if timer.corrected() {
+        mean + overhead
+    } else {
+        mean
+    };
+    log::info!(
+        "{}: empty-kernel overhead {:.4} ns per dispatch ({:.1}% of raw mean), results are {}",
+        label,
+        overhead,
+        100.0 * overhead / raw,
+        timer.label()
+    );
+
+    if let Some(peaks) = peaks {
+        let point = RooflinePoint::new(label.to_string(), cost, mean);
+        log::info!("{}: {}", label, point.summary(&peaks));
+
+        let dir = Roofline::dir(handle, &peaks);
+        let mut roofline = Roofline::load(&dir, peaks);
+        roofline.insert(point);
+        match roofline.save(&dir) {
+            Ok(()) => log::info!("Roofline plot written to {}", dir.display()),
+            Err(e) => log::warn!("Failed to write roofline plot: {:?}", e),
+        }
+    }
+    Some(BenchRecord::new(
+        kernel,
+        params.clone(),
+        AdapterRecord::from(handle),
+        &samples,
+        cost,
+        timer.corrected(),
+    ))
 }
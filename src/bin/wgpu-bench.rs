@@ -0,0 +1,261 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use wgpu_bencher::{
+    kernels, portability_table, read_records, to_csv, to_markdown, AdapterRecord, Baseline,
+    BenchRecord, GPUHandle, Golden, Params, Portability, RecordFormat, RegisteredKernel,
+    RegressionGate, Runnable, ValidationRecord, WgpuTimer,
+};
+
+/// Runs the registered kernels without criterion, writing JSON lines to stdout.
+#[derive(Parser)]
+#[command(name = "wgpu-bench")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Lists every registered kernel and its default parameters.
+    List,
+    /// Validates and times a kernel.
+    Run {
+        kernel: String,
+        /// Overrides a default parameter, e.g. --param M=2048
+        #[arg(short, long = "param", value_name = "KEY=VALUE")]
+        params: Vec<String>,
+        /// Number of timed samples.
+        #[arg(short, long, default_value_t = 10)]
+        samples: usize,
+        /// Times the kernel without checking its output.
+        #[arg(long)]
+        skip_validation: bool,
+        /// Format of the timing record: json, csv or markdown.
+        #[arg(short, long, default_value = "json")]
+        format: RecordFormat,
+    },
+    /// Only checks a kernel's output against its reference.
+    Validate {
+        kernel: String,
+        #[arg(short, long = "param", value_name = "KEY=VALUE")]
+        params: Vec<String>,
+    },
+    /// Lists the bindings of a kernel's shader, checking them against its tensors.
+    Bindings {
+        kernel: String,
+        #[arg(short, long = "param", value_name = "KEY=VALUE")]
+        params: Vec<String>,
+    },
+    /// Translates kernels to SPIR-V, MSL, HLSL and GLSL, exiting non-zero if any fail.
+    Portability {
+        /// Kernels to check, all registered kernels if none are given.
+        kernels: Vec<String>,
+        #[arg(short, long = "param", value_name = "KEY=VALUE")]
+        params: Vec<String>,
+        /// Writes each translation to DIR/<kernel>.<extension>.
+        #[arg(long, value_name = "DIR")]
+        dump: Option<PathBuf>,
+    },
+    /// Reruns the Python references of kernels and stores their inputs and outputs as the
+    /// goldens that validation replays.
+    RefreshGoldens {
+        /// Kernels to refresh, all registered kernels with a Python reference if none are given.
+        kernels: Vec<String>,
+        #[arg(short, long = "param", value_name = "KEY=VALUE")]
+        params: Vec<String>,
+        /// Seed of the inputs, WGPU_BENCH_SEED or 0 by default.
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Saves JSON results as a named baseline, replacing results for the same
+    /// kernel, parameters and adapter.
+    SaveBaseline {
+        /// Baseline name, or the path of a .json file.
+        name: String,
+        #[arg(required = true)]
+        results: Vec<PathBuf>,
+    },
+    /// Compares JSON results to a baseline, exiting non-zero on regression.
+    Compare {
+        /// Baseline name, or the path of a .json file.
+        name: String,
+        #[arg(required = true)]
+        results: Vec<PathBuf>,
+        /// Relative slowdown of the mean tolerated, e.g. 0.05 for 5%.
+        #[arg(short, long, default_value_t = RegressionGate::default().tolerance)]
+        tolerance: f64,
+        /// Significance level of Welch's t-test.
+        #[arg(short, long, default_value_t = RegressionGate::default().alpha)]
+        alpha: f64,
+    },
+}
+
+fn build(registered: &RegisteredKernel, params: &[String]) -> anyhow::Result<Box<dyn Runnable>> {
+    registered.build(&params.join(",").parse::<Params>()?)
+}
+
+fn validate(
+    registered: &RegisteredKernel,
+    kernel: &dyn Runnable,
+    handle: &GPUHandle,
+) -> anyhow::Result<ValidationRecord> {
+    let result = kernel.validate(handle);
+    let adapter = AdapterRecord::from(handle);
+    Ok(ValidationRecord::new(
+        &registered.name,
+        kernel.params(),
+        adapter,
+        &result,
+    ))
+}
+
+fn load_results(paths: &[PathBuf]) -> anyhow::Result<Vec<BenchRecord>> {
+    let mut records = vec![];
+    for path in paths {
+        records.extend(read_records(path)?);
+    }
+    Ok(records)
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let cli = Cli::parse();
+    let registry = kernels::registry();
+
+    match cli.command {
+        Command::List => {
+            for kernel in registry.iter() {
+                let entry = serde_json::json!({
+                    "kernel": kernel.name,
+                    "description": kernel.description,
+                    "defaults": kernel.defaults,
+                });
+                println!("{}", entry);
+            }
+        }
+        Command::Validate { kernel, params } => {
+            let registered = registry.get(&kernel)?;
+            let kernel = build(registered, &params)?;
+            let handle = pollster::block_on(GPUHandle::new())?;
+            let record = validate(registered, kernel.as_ref(), &handle)?;
+            println!("{}", serde_json::to_string(&record)?);
+            if !record.valid {
+                std::process::exit(1);
+            }
+        }
+        Command::Run {
+            kernel,
+            params,
+            samples,
+            skip_validation,
+            format,
+        } => {
+            let registered = registry.get(&kernel)?;
+            let kernel = build(registered, &params)?;
+            let handle = pollster::block_on(GPUHandle::new())?;
+            if !skip_validation {
+                //Kept off stdout, which only holds the timing record
+                let record = validate(registered, kernel.as_ref(), &handle)?;
+                eprintln!("{}", serde_json::to_string(&record)?);
+                if !record.valid {
+                    std::process::exit(1);
+                }
+            }
+            let adapter = AdapterRecord::from(&handle);
+            let timer = WgpuTimer::new(handle);
+            let samples = kernel.measure(&timer, samples);
+            let record = BenchRecord::new(
+                &registered.name,
+                kernel.params(),
+                adapter,
+                &samples,
+                kernel.cost(&kernel.tensors()),
+                timer.corrected(),
+            );
+            match format {
+                RecordFormat::Json => println!("{}", serde_json::to_string(&record)?),
+                RecordFormat::Csv => print!("{}", to_csv(&[record])),
+                RecordFormat::Markdown => println!("{}", to_markdown(&[record])),
+            }
+        }
+        Command::Bindings { kernel, params } => {
+            let registered = registry.get(&kernel)?;
+            for binding in build(registered, &params)?.bindings()? {
+                println!("{}", binding);
+            }
+        }
+        Command::Portability {
+            kernels,
+            params,
+            dump,
+        } => {
+            let names = match kernels.is_empty() {
+                true => registry.iter().map(|k| k.name.clone()).collect(),
+                false => kernels,
+            };
+            let mut reports = vec![];
+            for name in names {
+                let kernel = build(registry.get(&name)?, &params)?;
+                let report = Portability::check(name, &kernel.shader());
+                if let Some(dir) = &dump {
+                    report.dump(dir)?;
+                }
+                reports.push(report);
+            }
+            println!("{}", portability_table(&reports));
+            if !reports.iter().all(Portability::portable) {
+                std::process::exit(1);
+            }
+        }
+        Command::RefreshGoldens {
+            kernels,
+            params,
+            seed,
+        } => {
+            let seed = seed.map_or_else(Golden::seed, Ok)?;
+            let names = match kernels.is_empty() {
+                true => registry.iter().map(|k| k.name.clone()).collect(),
+                false => kernels.clone(),
+            };
+            for name in names {
+                match build(registry.get(&name)?, &params)?.record_golden(seed)? {
+                    Some(golden) => eprintln!("Refreshed {} in {}", name, golden.dir.display()),
+                    None if !kernels.is_empty() => {
+                        anyhow::bail!("{} has no Python reference to record", name)
+                    }
+                    None => {}
+                }
+            }
+        }
+        Command::SaveBaseline { name, results } => {
+            let path = Baseline::path(&name);
+            let mut baseline = match path.exists() {
+                true => Baseline::load(&path)?,
+                false => Baseline::default(),
+            };
+            baseline.extend(load_results(&results)?);
+            baseline.save(&path)?;
+            eprintln!(
+                "Saved {} results to baseline {}",
+                baseline.len(),
+                path.display()
+            );
+        }
+        Command::Compare {
+            name,
+            results,
+            tolerance,
+            alpha,
+        } => {
+            let baseline = Baseline::load(&Baseline::path(&name))?;
+            let report =
+                RegressionGate::new(tolerance, alpha).check(&baseline, &load_results(&results)?);
+            println!("{}", report);
+            if !report.passed() {
+                std::process::exit(1);
+            }
+        }
+    }
+    Ok(())
+}
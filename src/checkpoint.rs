@@ -0,0 +1,242 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use memmap2::Mmap;
+use safetensors::tensor::{Metadata, TensorInfo};
+
+use crate::storage::CPUStorage;
+use crate::{CPUTensor, DType, GPUHandle, GPUStorage, GPUTensor, NamedTensors, Shape, Tensor};
+
+//Metadata key marking a packed WQ8 tensor, with its logical shape as the value
+const WQ8_PREFIX: &str = "wq8.";
+
+//Copies the bytes once, into storage aligned for the dtype
+fn from_bytes(dt: DType, shape: Shape, bytes: &[u8]) -> CPUTensor {
+    let layout = std::alloc::Layout::from_size_align(bytes.len(), dt.size_of()).unwrap();
+    let data = if bytes.is_empty() {
+        std::ptr::null_mut()
+    } else {
+        let ptr = unsafe { std::alloc::alloc(layout) };
+        assert!(!ptr.is_null());
+        ptr
+    };
+    let mut storage = CPUStorage::new(data, layout);
+    if !bytes.is_empty() {
+        storage.as_bytes_mut().copy_from_slice(bytes);
+    }
+    Tensor::new(dt, shape, storage)
+}
+
+struct View<'a> {
+    dtype: safetensors::Dtype,
+    shape: Vec<usize>,
+    data: &'a [u8],
+}
+
+impl safetensors::View for View<'_> {
+    fn dtype(&self) -> safetensors::Dtype {
+        self.dtype
+    }
+
+    fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    fn data(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.data)
+    }
+
+    fn data_len(&self) -> usize {
+        self.data.len()
+    }
+}
+
+/// # Checkpoint
+///
+/// A memory-mapped `.safetensors` file, from which tensors are loaded by name so that only the
+/// tensors used are read. [`Checkpoint::load_gpu`] uploads a tensor straight from the page cache,
+/// [`Checkpoint::load`] copies it into a [`CPUTensor`].
+/// [`DType::WQ8`] tensors are stored as their packed bytes, with their shape in the metadata,
+/// so a [`Quantizer`](crate::Quantizer)'s output round-trips.
+pub struct Checkpoint {
+    path: PathBuf,
+    mmap: Mmap,
+    header: usize,
+    metadata: Metadata,
+}
+
+impl Checkpoint {
+    /// Maps the file, which must not be modified while the checkpoint is open.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)
+            .map_err(|e| anyhow::anyhow!("Failed to open {}: {}", path.display(), e))?;
+        let mmap = unsafe { Mmap::map(&file)? };
+        let (header, metadata) = safetensors::SafeTensors::read_metadata(&mmap)
+            .map_err(|e| anyhow::anyhow!("{}: {:?}", path.display(), e))?;
+        Ok(Self {
+            path,
+            mmap,
+            header,
+            metadata,
+        })
+    }
+
+    /// Names of the tensors, in the order they are stored.
+    pub fn names(&self) -> Vec<String> {
+        let mut tensors = self.metadata.tensors().into_iter().collect::<Vec<_>>();
+        tensors.sort_by_key(|(_, info)| info.data_offsets);
+        tensors.into_iter().map(|(name, _)| name).collect()
+    }
+
+    fn info(&self, name: &str) -> anyhow::Result<&TensorInfo> {
+        self.metadata
+            .info(name)
+            .ok_or_else(|| anyhow::anyhow!("{} has no tensor {}", self.path.display(), name))
+    }
+
+    pub fn dtype(&self, name: &str) -> anyhow::Result<DType> {
+        match self.wq8_shape(name)? {
+            Some(_) => Ok(DType::WQ8),
+            None => DType::try_from(self.info(name)?.dtype),
+        }
+    }
+
+    fn wq8_shape(&self, name: &str) -> anyhow::Result<Option<Shape>> {
+        let key = format!("{}{}", WQ8_PREFIX, name);
+        let Some(dims) = self.metadata.metadata().as_ref().and_then(|m| m.get(&key)) else {
+            return Ok(None);
+        };
+        let dims = dims
+            .split(',')
+            .filter(|d| !d.is_empty())
+            .map(|d| d.trim().parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| anyhow::anyhow!("{}: invalid WQ8 shape {}", name, dims))?;
+        Ok(Some(Shape::from(dims.as_slice())))
+    }
+
+    //The dtype, shape and mapped bytes of a tensor
+    fn locate(&self, name: &str) -> anyhow::Result<(DType, Shape, &[u8])> {
+        let info = self.info(name)?;
+        let (start, end) = info.data_offsets;
+        let bytes = &self.mmap[8 + self.header + start..8 + self.header + end];
+        match self.wq8_shape(name)? {
+            Some(shape) => {
+                anyhow::ensure!(
+                    info.dtype == safetensors::Dtype::U8,
+                    "{}: WQ8 tensors are stored as U8, got {:?}",
+                    name,
+                    info.dtype
+                );
+                Ok((DType::WQ8, shape, bytes))
+            }
+            None => {
+                let dt = DType::try_from(info.dtype)
+                    .map_err(|e| anyhow::anyhow!("{}: {:#}", name, e))?;
+                Ok((dt, Shape::from(info.shape.as_slice()), bytes))
+            }
+        }
+    }
+
+    /// Copies the tensor out of the mapped file.
+    pub fn load(&self, name: &str) -> anyhow::Result<CPUTensor> {
+        let (dt, shape, bytes) = self.locate(name)?;
+        Ok(from_bytes(dt, shape, bytes))
+    }
+
+    /// Uploads the tensor straight from the mapped file, without copying it to the host first.
+    pub fn load_gpu(&self, name: &str, handle: &GPUHandle) -> anyhow::Result<GPUTensor> {
+        let (dt, shape, bytes) = self.locate(name)?;
+        Ok(Tensor::new(
+            dt,
+            shape,
+            GPUStorage::from_bytes(handle, bytes),
+        ))
+    }
+
+    pub fn load_all(&self) -> anyhow::Result<NamedTensors> {
+        self.names()
+            .into_iter()
+            .map(|name| {
+                let tensor = self.load(&name)?;
+                Ok((name, tensor))
+            })
+            .collect()
+    }
+
+    /// Writes the tensors to a `.safetensors` file, replacing any file at `path`.
+    pub fn save(path: impl AsRef<Path>, tensors: &[(&str, &CPUTensor)]) -> anyhow::Result<()> {
+        let mut metadata = HashMap::new();
+        let views = tensors
+            .iter()
+            .map(|(name, tensor)| {
+                let shape = match tensor.dt() {
+                    DType::WQ8 => {
+                        let dims = tensor.shape().to_vec();
+                        let dims = dims.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+                        metadata.insert(format!("{}{}", WQ8_PREFIX, name), dims.join(","));
+                        vec![tensor.storage().as_bytes().len()]
+                    }
+                    _ => tensor.shape().to_vec(),
+                };
+                let view = View {
+                    dtype: tensor.dt().safetensors_type(),
+                    shape,
+                    data: tensor.storage().as_bytes(),
+                };
+                (name.to_string(), view)
+            })
+            .collect::<Vec<_>>();
+        let metadata = (!metadata.is_empty()).then_some(metadata);
+        safetensors::serialize_to_file(views, &metadata, path.as_ref())
+            .map_err(|e| anyhow::anyhow!("{}: {:?}", path.as_ref().display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn checkpoint_roundtrip() {
+        let dir = TempDir::new("ckpt");
+        let path = dir.join("model.safetensors");
+
+        let weight = CPUTensor::randn::<f32>(shape![32, 64]);
+        let quantizer = Quantizer::new(Quantization::SInt8);
+        let quantized = quantizer.quantize(weight.clone());
+        let bias = CPUTensor::from_slice(&[half::bf16::from_f32(0.5); 64], shape![64]);
+        let ids = CPUTensor::from_slice(&[3u32, 1, 4], shape![3]);
+        let tensors = [
+            ("w", &weight),
+            ("w_q", &quantized),
+            ("b", &bias),
+            ("ids", &ids),
+        ];
+        Checkpoint::save(&path, &tensors).unwrap();
+
+        let checkpoint = Checkpoint::open(&path).unwrap();
+        let mut names = checkpoint.names();
+        names.sort();
+        assert_eq!(names, ["b", "ids", "w", "w_q"]);
+        assert_eq!(checkpoint.dtype("b").unwrap(), DType::BF16);
+        assert_eq!(checkpoint.dtype("w_q").unwrap(), DType::WQ8);
+        for (name, tensor) in tensors {
+            let loaded = checkpoint.load(name).unwrap();
+            assert_eq!(loaded.dt(), tensor.dt());
+            assert_eq!(loaded.shape(), tensor.shape());
+            assert_eq!(loaded.storage().as_bytes(), tensor.storage().as_bytes());
+        }
+
+        //The loaded weight dequantizes exactly as the one that was stored
+        quantizer
+            .dequantize(checkpoint.load("w_q").unwrap())
+            .all_close(&quantizer.dequantize(quantized), 0.0, 0.0)
+            .unwrap();
+        assert_eq!(checkpoint.load_all().unwrap().len(), 4);
+        assert!(checkpoint.load("missing").is_err());
+    }
+}
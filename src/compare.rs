@@ -0,0 +1,228 @@
+use criterion::{BenchmarkId, Criterion};
+use tabled::{builder::Builder, settings::Style};
+
+use crate::{
+    bench_prepared, format_scaled, output_dir, save_results, CPUTensor, Cost, KernelBench,
+    Runnable, WgpuTimer, BYTE_UNITS,
+};
+
+type Expected = Box<dyn Fn(&[CPUTensor]) -> anyhow::Result<CPUTensor>>;
+
+/// Expected output shared by every variant, computed once.
+struct Reference {
+    output: usize,
+    atol: f32,
+    rtol: f32,
+    expected: Expected,
+}
+
+/// # Comparison
+///
+/// Several variants of the same op, timed in one criterion group on shared tensors
+/// and validated against one shared reference.
+pub struct Comparison {
+    op: String,
+    tensors: Vec<CPUTensor>,
+    variants: Vec<(String, Box<dyn Runnable>)>,
+    baseline: Option<String>,
+    reference: Option<Reference>,
+}
+
+impl Comparison {
+    pub fn new(op: impl Into<String>, tensors: Vec<CPUTensor>) -> Self {
+        Self {
+            op: op.into(),
+            tensors,
+            variants: vec![],
+            baseline: None,
+            reference: None,
+        }
+    }
+
+    /// Adds a variant, dispatched on the shared tensors instead of its own.
+    pub fn variant<K: KernelBench + 'static>(mut self, name: impl Into<String>, kernel: K) -> Self {
+        self.variants.push((name.into(), Box::new(kernel)));
+        self
+    }
+
+    /// Variant that speedups are relative to, defaults to the first.
+    pub fn baseline(mut self, name: impl Into<String>) -> Self {
+        self.baseline = Some(name.into());
+        self
+    }
+
+    /// Validates every variant's tensor at `output` against `expected`, computed once
+    /// from the shared tensors.
+    pub fn reference(
+        mut self,
+        output: usize,
+        atol: f32,
+        rtol: f32,
+        expected: impl Fn(&[CPUTensor]) -> anyhow::Result<CPUTensor> + 'static,
+    ) -> Self {
+        self.reference = Some(Reference {
+            output,
+            atol,
+            rtol,
+            expected: Box::new(expected),
+        });
+        self
+    }
+
+    fn baseline_index(&self) -> usize {
+        let Some(baseline) = &self.baseline else {
+            return 0;
+        };
+        self.variants
+            .iter()
+            .position(|(name, _)| name == baseline)
+            .unwrap_or_else(|| panic!("Baseline {} is not a variant of {}", baseline, self.op))
+    }
+}
+
+/// Result of a single variant in a [`Comparison`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComparisonRow {
+    pub variant: String,
+    pub cost: Cost,
+    pub ns: f64,      //Mean nanoseconds per dispatch
+    pub speedup: f64, //Relative to the baseline
+    pub valid: bool,  //Matched the reference
+    pub baseline: bool,
+}
+
+impl ComparisonRow {
+    /// Fills in the speedup of every row relative to `rows[baseline]`.
+    pub fn relative_to(rows: &mut [ComparisonRow], baseline: usize) {
+        let baseline_ns = rows[baseline].ns;
+        for (i, row) in rows.iter_mut().enumerate() {
+            row.speedup = baseline_ns / row.ns;
+            row.baseline = i == baseline;
+        }
+    }
+
+    /// Renders the rows as a markdown table.
+    pub fn table(rows: &[ComparisonRow]) -> String {
+        let mut builder = Builder::default();
+        builder.push_record(["Variant", "Mean", "Bandwidth", "Speedup", "Valid"]);
+        for row in rows {
+            let variant = match row.baseline {
+                true => format!("{} (baseline)", row.variant),
+                false => row.variant.clone(),
+            };
+            builder.push_record([
+                variant,
+                format!("{:.4} ns", row.ns),
+                format_scaled(row.cost.bytes as f64 * 1e9 / row.ns, &BYTE_UNITS),
+                format!("{:.2}x", row.speedup),
+                if row.valid { "PASS" } else { "FAIL" }.to_string(),
+            ]);
+        }
+        builder.build().with(Style::markdown()).to_string()
+    }
+}
+
+/// Times every variant of `comparison` in one criterion group and writes a table of their
+/// speedups relative to the baseline, returning its rows. Variants failing validation are timed
+/// and marked FAIL.
+pub fn benchmark_comparison(
+    c: &mut Criterion<&WgpuTimer>,
+    timer: &WgpuTimer,
+    comparison: Comparison,
+) -> Vec<ComparisonRow> {
+    let handle = timer.handle();
+    let peaks = timer.peaks();
+    let tensors = &comparison.tensors;
+    let expected = comparison.reference.as_ref().map(|reference| {
+        (reference.expected)(tensors)
+            .unwrap_or_else(|e| panic!("Reference for {} failed: {:?}", comparison.op, e))
+    });
+
+    let (mut rows, mut records) = (vec![], vec![]);
+    let mut group = c.benchmark_group(&comparison.op);
+    for (name, variant) in &comparison.variants {
+        let kernel = format!("{}/{}", comparison.op, name);
+        let validation = match (&comparison.reference, &expected) {
+            (Some(reference), Some(expected)) => {
+                let mut outputs = variant.outputs(handle, tensors);
+                outputs
+                    .remove(reference.output)
+                    .into_cpu(handle)
+                    .and_then(|output| expected.all_close(&output, reference.atol, reference.rtol))
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = &validation {
+            log::warn!("{}: precision FAIL: {:?}", kernel, e);
+        }
+
+        let cost = variant.cost(tensors);
+        let prepared = variant.prepare(handle, tensors);
+        let id = BenchmarkId::from_parameter(name);
+        let record = bench_prepared(
+            &mut group,
+            timer,
+            id,
+            &kernel,
+            &variant.params(),
+            &prepared,
+            cost,
+            variant.dispatches(),
+            peaks,
+        );
+        rows.push(ComparisonRow {
+            variant: name.clone(),
+            cost,
+            ns: record.as_ref().map_or(f64::NAN, |r| r.mean_ns),
+            speedup: 1.0,
+            valid: validation.is_ok(),
+            baseline: false,
+        });
+        records.extend(record);
+    }
+    group.finish();
+    save_results(&comparison.op, &records);
+
+    if !rows.is_empty() {
+        ComparisonRow::relative_to(&mut rows, comparison.baseline_index());
+        let table = ComparisonRow::table(&rows);
+        let path = output_dir()
+            .join("results")
+            .join(format!("{}.comparison.md", comparison.op));
+        match std::fs::write(&path, table) {
+            Ok(()) => log::info!("Comparison table written to {}", path.display()),
+            Err(e) => log::warn!("Failed to write {}: {:?}", path.display(), e),
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn comparison_table() {
+        let row = |variant: &str, ns: f64, valid: bool| ComparisonRow {
+            variant: variant.to_string(),
+            cost: Cost::new(0, 1_000_000),
+            ns,
+            speedup: 1.0,
+            valid,
+            baseline: false,
+        };
+        let mut rows = vec![
+            row("Naive", 200e3, true),
+            row("Welford", 50e3, true),
+            row("OnePass", 100e3, false),
+        ];
+        ComparisonRow::relative_to(&mut rows, 0);
+        assert_eq!(rows[1].speedup, 4.0);
+        assert_eq!(rows[2].speedup, 2.0);
+
+        let table = ComparisonRow::table(&rows);
+        assert!(table.contains("Naive (baseline)"));
+        assert!(table.contains("| 4.00x"));
+        assert!(table.contains("FAIL"));
+    }
+}
@@ -70,29 +70,94 @@ impl DType {
     }
 }
 
+impl std::str::FromStr for DType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "q8" => DType::Q8,
+            "f16" => DType::F16,
+            "bf16" => DType::BF16,
+            "f32" => DType::F32,
+            "i32" => DType::I32,
+            "u32" => DType::U32,
+            "wq8" => DType::WQ8,
+            _ => anyhow::bail!("Unknown dtype {}", s),
+        })
+    }
+}
+
 impl DType {
-    fn handle_type_str(ts: npyz::TypeStr) -> DType {
-        match ts.endianness() {
-            npyz::Endianness::Little => match (ts.type_char(), ts.size_field()) {
-                (npyz::TypeChar::Float, 4) => DType::F32,
-                (npyz::TypeChar::Int, 4) => DType::I32,
-                (npyz::TypeChar::Uint, 4) => DType::U32,
-                (t, s) => unimplemented!("{} {}", t, s),
-            },
-            _ => unimplemented!(),
-        }
+    /// The little-endian `.npy` type of the dtype. bf16 has no numpy equivalent and is stored
+    /// as raw 2 byte values (`|V2`), which `ml_dtypes.bfloat16` can view.
+    pub fn npy_type(self) -> anyhow::Result<npyz::DType> {
+        let descr = match self {
+            DType::Q8 => "|i1",
+            DType::F16 => "<f2",
+            DType::BF16 => "|V2",
+            DType::F32 => "<f4",
+            DType::I32 => "<i4",
+            DType::U32 => "<u4",
+            DType::WQ8 => anyhow::bail!(
+                "WQ8 packs weights and scales into one buffer and has no .npy type, dequantize it first"
+            ),
+        };
+        Ok(npyz::DType::Plain(descr.parse()?))
     }
 }
 
-impl From<npyz::DType> for DType {
-    fn from(dtype: npyz::DType) -> Self {
-        match dtype {
-            npyz::DType::Plain(ts) => Self::handle_type_str(ts),
-            _ => unimplemented!(),
+//Either byte order maps to the same dtype, values are swapped as they are read
+impl TryFrom<npyz::DType> for DType {
+    type Error = anyhow::Error;
+
+    fn try_from(dtype: npyz::DType) -> anyhow::Result<Self> {
+        use npyz::TypeChar;
+        let npyz::DType::Plain(ts) = &dtype else {
+            anyhow::bail!("Unsupported .npy type {}", dtype.descr());
+        };
+        Ok(match (ts.type_char(), ts.size_field()) {
+            (TypeChar::Int, 1) => DType::Q8,
+            (TypeChar::Float, 2) => DType::F16,
+            (TypeChar::RawData, 2) => DType::BF16,
+            (TypeChar::Float, 4) => DType::F32,
+            (TypeChar::Int, 4) => DType::I32,
+            (TypeChar::Uint, 4) => DType::U32,
+            _ => anyhow::bail!("Unsupported .npy type {}", ts),
+        })
+    }
+}
+
+impl DType {
+    /// The safetensors dtype. WQ8 has none and is stored as its packed bytes, see [`Checkpoint`].
+    pub fn safetensors_type(self) -> safetensors::Dtype {
+        match self {
+            DType::Q8 => safetensors::Dtype::I8,
+            DType::F16 => safetensors::Dtype::F16,
+            DType::BF16 => safetensors::Dtype::BF16,
+            DType::F32 => safetensors::Dtype::F32,
+            DType::I32 => safetensors::Dtype::I32,
+            DType::U32 => safetensors::Dtype::U32,
+            DType::WQ8 => safetensors::Dtype::U8,
         }
     }
 }
 
+impl TryFrom<safetensors::Dtype> for DType {
+    type Error = anyhow::Error;
+
+    fn try_from(dtype: safetensors::Dtype) -> anyhow::Result<Self> {
+        Ok(match dtype {
+            safetensors::Dtype::I8 => DType::Q8,
+            safetensors::Dtype::F16 => DType::F16,
+            safetensors::Dtype::BF16 => DType::BF16,
+            safetensors::Dtype::F32 => DType::F32,
+            safetensors::Dtype::I32 => DType::I32,
+            safetensors::Dtype::U32 => DType::U32,
+            _ => anyhow::bail!("Unsupported safetensors type {:?}", dtype),
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct BufferSegment {
     pub offset: BufferAddress,
@@ -147,6 +212,7 @@ macro_rules! map_half_type {
     };
 }
 
+map_type!(i8, Q8);
 map_type!(f32, F32);
 map_type!(i32, I32);
 map_type!(u32, U32);
@@ -0,0 +1,236 @@
+use std::{fmt::Display, path::Path, str::FromStr};
+
+use tabled::{builder::Builder, settings::Style};
+
+use crate::{format_scaled, output_dir, BenchRecord, BYTE_UNITS, FLOP_UNITS};
+
+/// A format [`BenchRecord`]s can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    Json,
+    Csv,
+    Markdown,
+}
+
+impl RecordFormat {
+    pub const ALL: [RecordFormat; 3] = [
+        RecordFormat::Json,
+        RecordFormat::Csv,
+        RecordFormat::Markdown,
+    ];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordFormat::Json => "json",
+            RecordFormat::Csv => "csv",
+            RecordFormat::Markdown => "md",
+        }
+    }
+
+    pub fn render(&self, records: &[BenchRecord]) -> anyhow::Result<String> {
+        Ok(match self {
+            RecordFormat::Json => serde_json::to_string_pretty(records)?,
+            RecordFormat::Csv => to_csv(records),
+            RecordFormat::Markdown => to_markdown(records),
+        })
+    }
+}
+
+impl FromStr for RecordFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(RecordFormat::Json),
+            "csv" => Ok(RecordFormat::Csv),
+            "md" | "markdown" => Ok(RecordFormat::Markdown),
+            _ => anyhow::bail!("Unknown format {}, expected json, csv or markdown", s),
+        }
+    }
+}
+
+impl Display for RecordFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+const CSV_HEADER: [&str; 17] = [
+    "kernel",
+    "params",
+    "adapter",
+    "backend",
+    "driver",
+    "fingerprint",
+    "samples",
+    "mean_ns",
+    "median_ns",
+    "min_ns",
+    "max_ns",
+    "stddev_ns",
+    "overhead_subtracted",
+    "flops",
+    "bytes",
+    "flops_per_s",
+    "bytes_per_s",
+];
+
+//Quotes fields containing separators, e.g. the commas between params
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// One row per record, with every field of [`BenchRecord`] flattened into a column.
+pub fn to_csv(records: &[BenchRecord]) -> String {
+    let mut csv = CSV_HEADER.join(",") + "\n";
+    for r in records {
+        let row = [
+            r.kernel.clone(),
+            r.params.to_string(),
+            r.adapter.name.clone(),
+            r.adapter.backend.clone(),
+            r.adapter.driver.clone(),
+            r.adapter.fingerprint.clone(),
+            r.samples.to_string(),
+            r.mean_ns.to_string(),
+            r.median_ns.to_string(),
+            r.min_ns.to_string(),
+            r.max_ns.to_string(),
+            r.stddev_ns.to_string(),
+            r.overhead_subtracted.to_string(),
+            r.flops.to_string(),
+            r.bytes.to_string(),
+            r.flops_per_s.to_string(),
+            r.bytes_per_s.to_string(),
+        ];
+        let row = row.iter().map(|f| csv_field(f)).collect::<Vec<_>>();
+        csv += &(row.join(",") + "\n");
+    }
+    csv
+}
+
+/// A Markdown table of the records, ready to paste into a README.
+pub fn to_markdown(records: &[BenchRecord]) -> String {
+    let mut builder = Builder::default();
+    builder.push_record([
+        "Kernel",
+        "Params",
+        "Adapter",
+        "Mean",
+        "Median",
+        "Min",
+        "Max",
+        "Compute",
+        "Bandwidth",
+    ]);
+    for r in records {
+        builder.push_record([
+            r.kernel.clone(),
+            r.params.to_string(),
+            format!("{} ({})", r.adapter.name, r.adapter.backend),
+            format!("{:.4} ns", r.mean_ns),
+            format!("{:.4} ns", r.median_ns),
+            format!("{:.4} ns", r.min_ns),
+            format!("{:.4} ns", r.max_ns),
+            format_scaled(r.flops_per_s, &FLOP_UNITS),
+            format_scaled(r.bytes_per_s, &BYTE_UNITS),
+        ]);
+    }
+    builder.build().with(Style::markdown()).to_string()
+}
+
+/// Writes `records` to `path`, in the format given by its extension.
+pub fn write_records(path: &Path, records: &[BenchRecord]) -> anyhow::Result<()> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    let contents = extension.parse::<RecordFormat>()?.render(records)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Reads records written as a JSON array, or as JSON lines by `wgpu-bench run`.
+pub fn read_records(path: &Path) -> anyhow::Result<Vec<BenchRecord>> {
+    let contents = std::fs::read_to_string(path)?;
+    if contents.trim_start().starts_with('[') {
+        return Ok(serde_json::from_str(&contents)?);
+    }
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+/// Writes the records of a benchmark group to `target/wgpu-bench/results/<group>.{json,csv,md}`.
+pub fn save_results(group: &str, records: &[BenchRecord]) {
+    let dir = output_dir().join("results");
+    let stem = group.replace(['/', '\\', ' '], "_");
+    for format in RecordFormat::ALL {
+        let path = dir.join(format!("{}.{}", stem, format.extension()));
+        if let Err(e) = write_records(&path, records) {
+            log::warn!("Failed to write {}: {:?}", path.display(), e);
+        }
+    }
+    log::info!(
+        "Results written to {}/{}.{{json,csv,md}}",
+        dir.display(),
+        stem
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn record_export() {
+        let adapter = AdapterRecord {
+            name: "Apple M3 Max".to_string(),
+            backend: "Metal".to_string(),
+            driver: String::new(),
+            fingerprint: "Apple_M3_Max-Metal-0000-0000-".to_string(),
+        };
+        let cost = Cost::new(2_000_000, 1_000_000);
+        let record = BenchRecord::new(
+            "SGEMM",
+            params![M = 1024, N = 1024],
+            adapter,
+            &[1e3, 3e3, 2e3],
+            cost,
+            false,
+        );
+        assert_eq!(record.mean_ns, 2e3);
+        assert_eq!(record.median_ns, 2e3);
+        assert_eq!(record.stddev_ns, 1e3);
+        assert_eq!(record.bytes_per_s, 5e11);
+
+        let records = vec![record];
+        let csv = to_csv(&records);
+        let row = csv.lines().nth(1).unwrap();
+        assert!(row.starts_with("SGEMM,\"M=1024,N=1024\",Apple M3 Max,Metal,,"));
+        assert_eq!(csv.lines().next().unwrap().split(',').count(), 17);
+
+        let markdown = to_markdown(&records);
+        assert!(markdown.contains("| SGEMM "));
+        assert!(markdown.contains("1.0000 TFLOP/s"));
+
+        let json = RecordFormat::Json.render(&records).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<BenchRecord>>(&json).unwrap(),
+            records
+        );
+        assert_eq!(
+            "markdown".parse::<RecordFormat>().unwrap(),
+            RecordFormat::Markdown
+        );
+    }
+}
@@ -0,0 +1,405 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::Params;
+
+/// The value of an [`Expr`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Bool(bool),
+}
+
+impl Value {
+    pub fn as_f64(&self) -> anyhow::Result<f64> {
+        match self {
+            Value::Number(n) => Ok(*n),
+            Value::Bool(b) => anyhow::bail!("Expected a number, got {}", b),
+        }
+    }
+
+    pub fn as_bool(&self) -> anyhow::Result<bool> {
+        match self {
+            Value::Bool(b) => Ok(*b),
+            Value::Number(n) => anyhow::bail!("Expected a bool, got {}", n),
+        }
+    }
+
+    /// A non-negative integer, e.g. a dimension or workgroup count.
+    pub fn as_usize(&self) -> anyhow::Result<usize> {
+        let n = self.as_f64()?;
+        anyhow::ensure!(
+            n >= 0.0 && n.fract() == 0.0,
+            "Expected a non-negative integer, got {}",
+            n
+        );
+        Ok(n as usize)
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinOp {
+    const SYMBOLS: [(&'static str, BinOp); 13] = [
+        ("||", BinOp::Or),
+        ("&&", BinOp::And),
+        ("==", BinOp::Eq),
+        ("!=", BinOp::Ne),
+        ("<=", BinOp::Le),
+        (">=", BinOp::Ge),
+        ("<", BinOp::Lt),
+        (">", BinOp::Gt),
+        ("+", BinOp::Add),
+        ("-", BinOp::Sub),
+        ("*", BinOp::Mul),
+        ("/", BinOp::Div),
+        ("%", BinOp::Rem),
+    ];
+
+    //Higher binds tighter
+    fn precedence(&self) -> u8 {
+        match self {
+            BinOp::Or => 0,
+            BinOp::And => 1,
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 2,
+            BinOp::Add | BinOp::Sub => 3,
+            BinOp::Mul | BinOp::Div | BinOp::Rem => 4,
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        Self::SYMBOLS.iter().find(|(_, op)| op == self).unwrap().0
+    }
+
+    fn apply(&self, lhs: Value, rhs: Value) -> anyhow::Result<Value> {
+        use Value::*;
+        Ok(match (self, lhs, rhs) {
+            (BinOp::Or, Bool(a), Bool(b)) => Bool(a || b),
+            (BinOp::And, Bool(a), Bool(b)) => Bool(a && b),
+            (BinOp::Eq, a, b) => Bool(a == b),
+            (BinOp::Ne, a, b) => Bool(a != b),
+            (BinOp::Lt, Number(a), Number(b)) => Bool(a < b),
+            (BinOp::Le, Number(a), Number(b)) => Bool(a <= b),
+            (BinOp::Gt, Number(a), Number(b)) => Bool(a > b),
+            (BinOp::Ge, Number(a), Number(b)) => Bool(a >= b),
+            (BinOp::Add, Number(a), Number(b)) => Number(a + b),
+            (BinOp::Sub, Number(a), Number(b)) => Number(a - b),
+            (BinOp::Mul, Number(a), Number(b)) => Number(a * b),
+            (BinOp::Div, Number(a), Number(b)) => Number(a / b),
+            (BinOp::Rem, Number(a), Number(b)) => Number(a % b),
+            (op, a, b) => anyhow::bail!("Cannot apply {} to {} and {}", op.symbol(), a, b),
+        })
+    }
+}
+
+/// # Expr
+///
+/// An arithmetic expression over named parameters, as written in kernel manifests, e.g.
+/// `ceil_div(M * N, 256)` or `K % 4 == 0`. Numbers are `f64`, so integers are exact up to 2^53.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Var(String),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+impl Expr {
+    pub fn eval(&self, params: &Params) -> anyhow::Result<Value> {
+        match self {
+            Expr::Literal(value) => Ok(*value),
+            Expr::Var(name) => {
+                let value = params.get::<String>(name)?;
+                match value.parse::<bool>() {
+                    Ok(b) => Ok(Value::Bool(b)),
+                    Err(_) => Ok(Value::Number(params.get(name)?)),
+                }
+            }
+            Expr::Neg(e) => Ok(Value::Number(-e.eval(params)?.as_f64()?)),
+            Expr::Not(e) => Ok(Value::Bool(!e.eval(params)?.as_bool()?)),
+            Expr::Binary(op, lhs, rhs) => op.apply(lhs.eval(params)?, rhs.eval(params)?),
+            Expr::Call(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|a| a.eval(params))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                call(name, &args)
+            }
+        }
+    }
+
+    pub fn eval_usize(&self, params: &Params) -> anyhow::Result<usize> {
+        self.eval(params)?
+            .as_usize()
+            .map_err(|e| anyhow::anyhow!("{}: {}", self, e))
+    }
+}
+
+//`select(f, t, cond)` as in WGSL, every other function takes numbers
+fn call(name: &str, args: &[Value]) -> anyhow::Result<Value> {
+    let arity = |n: usize| {
+        anyhow::ensure!(
+            args.len() == n,
+            "{} takes {} arguments, got {}",
+            name,
+            n,
+            args.len()
+        );
+        Ok(())
+    };
+    if name == "select" {
+        arity(3)?;
+        return Ok(if args[2].as_bool()? { args[1] } else { args[0] });
+    }
+    let args = args
+        .iter()
+        .map(Value::as_f64)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let result = match name {
+        "min" | "max" => {
+            anyhow::ensure!(!args.is_empty(), "{} takes at least one argument", name);
+            let fold = if name == "min" { f64::min } else { f64::max };
+            args.iter().copied().reduce(fold).unwrap()
+        }
+        "ceil_div" => {
+            arity(2)?;
+            (args[0] / args[1]).ceil()
+        }
+        "ceil" | "floor" | "log2" | "sqrt" => {
+            arity(1)?;
+            match name {
+                "ceil" => args[0].ceil(),
+                "floor" => args[0].floor(),
+                "log2" => args[0].log2(),
+                _ => args[0].sqrt(),
+            }
+        }
+        "pow" => {
+            arity(2)?;
+            args[0].powf(args[1])
+        }
+        _ => anyhow::bail!("Unknown function {}", name),
+    };
+    Ok(Value::Number(result))
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, expected: &str) -> anyhow::Error {
+        let position = self.source.len() - self.rest.len();
+        anyhow::anyhow!("Expected {} at {} in {:?}", expected, position, self.source)
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn binary_op(&mut self) -> Option<BinOp> {
+        self.skip_whitespace();
+        BinOp::SYMBOLS
+            .iter()
+            .find(|(symbol, _)| self.rest.starts_with(symbol))
+            .map(|(_, op)| *op)
+    }
+
+    //Precedence climbing over binary operators of at least `min_precedence`
+    fn expr(&mut self, min_precedence: u8) -> anyhow::Result<Expr> {
+        let mut lhs = self.unary()?;
+        while let Some(op) = self.binary_op() {
+            if op.precedence() < min_precedence {
+                break;
+            }
+            self.eat(op.symbol());
+            let rhs = self.expr(op.precedence() + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Expr> {
+        if self.eat("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> anyhow::Result<Expr> {
+        if self.eat("(") {
+            let inner = self.expr(0)?;
+            return match self.eat(")") {
+                true => Ok(inner),
+                false => Err(self.error("`)`")),
+            };
+        }
+        self.skip_whitespace();
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(self.rest.len());
+        let token = &self.rest[..end];
+        if token.is_empty() {
+            return Err(self.error("a number, parameter or `(`"));
+        }
+        self.rest = &self.rest[end..];
+        if token.starts_with(|c: char| c.is_ascii_digit()) {
+            return token
+                .parse()
+                .map(|n| Expr::Literal(Value::Number(n)))
+                .map_err(|_| anyhow::anyhow!("Invalid number {} in {:?}", token, self.source));
+        }
+        match token {
+            "true" => return Ok(Expr::Literal(Value::Bool(true))),
+            "false" => return Ok(Expr::Literal(Value::Bool(false))),
+            _ => {}
+        }
+        if !self.eat("(") {
+            return Ok(Expr::Var(token.to_string()));
+        }
+        let mut args = vec![];
+        if !self.eat(")") {
+            loop {
+                args.push(self.expr(0)?);
+                if self.eat(")") {
+                    break;
+                }
+                if !self.eat(",") {
+                    return Err(self.error("`,` or `)`"));
+                }
+            }
+        }
+        Ok(Expr::Call(token.to_string(), args))
+    }
+}
+
+impl FromStr for Expr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { source: s, rest: s };
+        let expr = parser.expr(0)?;
+        parser.skip_whitespace();
+        match parser.rest.is_empty() {
+            true => Ok(expr),
+            false => Err(parser.error("an operator")),
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::Var(name) => write!(f, "{}", name),
+            Expr::Neg(e) => write!(f, "-{}", e),
+            Expr::Not(e) => write!(f, "!{}", e),
+            Expr::Binary(op, lhs, rhs) => write!(f, "({} {} {})", lhs, op.symbol(), rhs),
+            Expr::Call(name, args) => {
+                let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+        }
+    }
+}
+
+//Written in TOML as a string, or as a plain number or bool
+impl<'de> serde::Deserialize<'de> for Expr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Bool(bool),
+            Int(i64),
+            Float(f64),
+            Text(String),
+        }
+        Ok(match Raw::deserialize(deserializer)? {
+            Raw::Bool(b) => Expr::Literal(Value::Bool(b)),
+            Raw::Int(i) => Expr::Literal(Value::Number(i as f64)),
+            Raw::Float(f) => Expr::Literal(Value::Number(f)),
+            Raw::Text(s) => s.parse().map_err(serde::de::Error::custom)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn expressions() {
+        let p = params![M = 1023, N = 512, TRANS = false, eps = 1e-5];
+        let eval = |s: &str| s.parse::<Expr>().unwrap().eval(&p).unwrap();
+        assert_eq!(eval("ceil_div(M, 16) * 16"), Value::Number(1024.0));
+        assert_eq!(eval("2 + 3 * 4 - 6 / 2"), Value::Number(11.0));
+        assert_eq!(eval("-(M % 4) + max(N, 8, 1024)"), Value::Number(1021.0));
+        assert_eq!(eval("N % 4 == 0 && !TRANS"), Value::Bool(true));
+        assert_eq!(eval("M < N || TRANS"), Value::Bool(false));
+        assert_eq!(eval("eps * 1e5"), Value::Number(1.0));
+        assert_eq!(eval("select(1, 4, N % 4 == 0)"), Value::Number(4.0));
+
+        let error = |s: &str| match s.parse::<Expr>() {
+            Ok(e) => e.eval(&p).unwrap_err().to_string(),
+            Err(e) => e.to_string(),
+        };
+        assert_eq!(
+            error("M +"),
+            "Expected a number, parameter or `(` at 3 in \"M +\""
+        );
+        assert_eq!(error("(M"), "Expected `)` at 2 in \"(M\"");
+        assert_eq!(error("M N"), "Expected an operator at 2 in \"M N\"");
+        assert!(error("K").starts_with("Missing parameter K"));
+        assert_eq!(error("TRANS + 1"), "Cannot apply + to false and 1");
+        assert_eq!(
+            "M / 2"
+                .parse::<Expr>()
+                .unwrap()
+                .eval_usize(&p)
+                .unwrap_err()
+                .to_string(),
+            "(M / 2): Expected a non-negative integer, got 511.5"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::path::{Path, PathBuf};
+
+use crate::{CPUTensor, Params};
+
+const INDEX: &str = "golden.json";
+
+/// Tensors stored in a golden, with their names.
+pub type NamedTensors = Vec<(String, CPUTensor)>;
+
+//Names of the stored tensors in order, written last so that an interrupted store isn't replayed
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct GoldenIndex {
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+}
+
+/// # Golden
+///
+/// A reference output stored with the inputs it was computed from, so that a reference needing
+/// Python only runs once. Goldens are kept in `goldens/<kernel>/<params>/seed=<seed>/` as
+/// `inputs/<name>.npy` and `outputs/<name>.npy`, indexed by `golden.json`, and can be committed so
+/// that machines without Python replay them.
+#[derive(Debug, Clone)]
+pub struct Golden {
+    pub dir: PathBuf,
+}
+
+impl Golden {
+    /// Directory the goldens are kept in, `goldens/` in the crate root by default.
+    pub const DIR_VAR: &'static str = "WGPU_BENCH_GOLDENS";
+    /// Seed of the inputs kernels are validated with, 0 by default.
+    pub const SEED_VAR: &'static str = "WGPU_BENCH_SEED";
+
+    pub fn root() -> PathBuf {
+        std::env::var_os(Self::DIR_VAR).map_or_else(
+            || Path::new(env!("CARGO_MANIFEST_DIR")).join("goldens"),
+            PathBuf::from,
+        )
+    }
+
+    /// The seed requested through `WGPU_BENCH_SEED`.
+    pub fn seed() -> anyhow::Result<u64> {
+        match std::env::var(Self::SEED_VAR) {
+            Ok(seed) => seed.parse().map_err(|_| {
+                anyhow::anyhow!("{} must be an integer, got {}", Self::SEED_VAR, seed)
+            }),
+            Err(_) => Ok(0),
+        }
+    }
+
+    pub fn new(kernel: &str, params: &Params, seed: u64) -> Self {
+        let params = match params.is_empty() {
+            true => "default".to_string(),
+            false => params.to_string(),
+        };
+        let dir = Self::root()
+            .join(kernel)
+            .join(params)
+            .join(format!("seed={}", seed));
+        Self { dir }
+    }
+
+    pub fn exists(&self) -> bool {
+        self.dir.join(INDEX).exists()
+    }
+
+    /// Replaces the golden with `inputs` and the `outputs` expected from them.
+    pub fn store(
+        &self,
+        inputs: &[(&str, &CPUTensor)],
+        outputs: &[(&str, &CPUTensor)],
+    ) -> anyhow::Result<()> {
+        if self.dir.exists() {
+            std::fs::remove_dir_all(&self.dir)?;
+        }
+        let names = |kind: &str, tensors: &[(&str, &CPUTensor)]| {
+            let dir = self.dir.join(kind);
+            std::fs::create_dir_all(&dir)?;
+            tensors
+                .iter()
+                .map(|(name, tensor)| {
+                    tensor.write_npy(dir.join(format!("{}.npy", name)))?;
+                    Ok(name.to_string())
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        };
+        let index = GoldenIndex {
+            inputs: names("inputs", inputs)?,
+            outputs: names("outputs", outputs)?,
+        };
+        std::fs::write(self.dir.join(INDEX), serde_json::to_string_pretty(&index)?)?;
+        Ok(())
+    }
+
+    /// The stored inputs and expected outputs, by name.
+    pub fn load(&self) -> anyhow::Result<(NamedTensors, NamedTensors)> {
+        let index = std::fs::read_to_string(self.dir.join(INDEX))
+            .map_err(|e| anyhow::anyhow!("No golden in {}: {}", self.dir.display(), e))?;
+        let index: GoldenIndex = serde_json::from_str(&index)?;
+        let load = |kind: &str, names: Vec<String>| {
+            let dir = self.dir.join(kind);
+            names
+                .into_iter()
+                .map(|name| {
+                    let tensor = CPUTensor::read_npy(dir.join(format!("{}.npy", name)))?;
+                    Ok((name, tensor))
+                })
+                .collect::<anyhow::Result<NamedTensors>>()
+        };
+        Ok((
+            load("inputs", index.inputs)?,
+            load("outputs", index.outputs)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn golden_roundtrip() {
+        let dir = TempDir::new("golden");
+        let golden = Golden {
+            dir: dir.join("seed=7"),
+        };
+        assert!(!golden.exists());
+
+        let a = CPUTensor::randn::<f32>(shape![2, 3]);
+        let n = CPUTensor::from_slice(&[1u32, 2, 3], shape![3]);
+        let y = CPUTensor::randn::<f32>(shape![2, 3]);
+        golden.store(&[("A", &a), ("n", &n)], &[("Y", &y)]).unwrap();
+        assert!(golden.exists());
+        assert!(golden.dir.join("outputs/Y.npy").exists());
+
+        let (inputs, outputs) = golden.load().unwrap();
+        let names = inputs
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["A", "n"]);
+        inputs[0].1.all_close(&a, 0.0, 0.0).unwrap();
+        assert_eq!(inputs[1].1.to_vec::<u32>().unwrap(), [1, 2, 3]);
+        assert_eq!(outputs[0].0, "Y");
+        outputs[0].1.all_close(&y, 0.0, 0.0).unwrap();
+
+        //Storing again replaces the golden rather than adding to it
+        golden.store(&[("B", &a)], &[]).unwrap();
+        let (inputs, outputs) = golden.load().unwrap();
+        assert_eq!(inputs.len(), 1);
+        assert!(outputs.is_empty());
+        assert!(!golden.dir.join("inputs/A.npy").exists());
+    }
+}
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use naga::valid::Capabilities;
 use wgpu::Adapter;
 use wgpu::DeviceType;
 
@@ -17,6 +18,7 @@ pub struct GPUHandle {
 pub struct Inner {
     device: wgpu::Device,
     queue: wgpu::Queue,
+    info: wgpu::AdapterInfo,
 }
 
 impl std::ops::Deref for GPUHandle {
@@ -28,10 +30,17 @@ impl std::ops::Deref for GPUHandle {
 }
 
 impl GPUHandle {
-    fn get_features() -> wgpu::Features {
-        wgpu::Features::default()
-            | wgpu::Features::TIMESTAMP_QUERY
-            | wgpu::Features::SUBGROUP_COMPUTE
+    fn get_features(adapter: &Adapter) -> wgpu::Features {
+        let mut features = wgpu::Features::default() | wgpu::Features::SUBGROUP_COMPUTE;
+        //Software adapters (llvmpipe, lavapipe) don't support timestamps
+        if adapter.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            features |= wgpu::Features::TIMESTAMP_QUERY;
+            //Used to time each dispatch separately
+            features |= adapter.features() & wgpu::Features::TIMESTAMP_QUERY_INSIDE_PASSES;
+        } else {
+            log::warn!("Adapter does not support TIMESTAMP_QUERY, falling back to wall-clock");
+        }
+        features
     }
 
     pub async fn new() -> Result<Self, anyhow::Error> {
@@ -39,7 +48,7 @@ impl GPUHandle {
 
         let mut device_descriptor = wgpu::DeviceDescriptor {
             label: Some("rumble"),
-            required_features: Self::get_features(),
+            required_features: Self::get_features(&adapter),
             required_limits: Limits {
                 max_buffer_size: (2 << 29) - 1,
                 max_storage_buffer_binding_size: (2 << 29) - 1,
@@ -59,7 +68,11 @@ impl GPUHandle {
         };
 
         Ok(Self {
-            inner: Arc::new(Inner { device, queue }),
+            inner: Arc::new(Inner {
+                device,
+                queue,
+                info: adapter.get_info(),
+            }),
         })
     }
 
@@ -71,6 +84,79 @@ impl GPUHandle {
         &self.queue
     }
 
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.info
+    }
+
+    /// Identifies the adapter and driver, for results that only hold on the same device.
+    pub fn fingerprint(&self) -> String {
+        let info = &self.info;
+        format!(
+            "{}-{:?}-{:04x}-{:04x}-{}",
+            info.name, info.backend, info.vendor, info.device, info.driver
+        )
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c,
+            _ => '_',
+        })
+        .collect()
+    }
+
+    /// Whether GPU timestamps can be written at compute pass boundaries.
+    pub fn supports_timestamps(&self) -> bool {
+        self.device()
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+    }
+
+    /// Whether GPU timestamps can be written between dispatches inside a compute pass.
+    pub fn supports_timestamps_inside_passes(&self) -> bool {
+        self.device()
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY_INSIDE_PASSES)
+    }
+
+    /// Shader capabilities the device's features allow, for validating shaders with naga
+    /// before they reach `create_shader_module_unchecked`.
+    pub fn capabilities(&self) -> Capabilities {
+        use wgpu::Features;
+        //Subgroups are required when the device is created, so they are always allowed
+        let optional = [
+            (Features::SHADER_F64, Capabilities::FLOAT64),
+            (Features::PUSH_CONSTANTS, Capabilities::PUSH_CONSTANT),
+            (
+                Features::SHADER_PRIMITIVE_INDEX,
+                Capabilities::PRIMITIVE_INDEX,
+            ),
+            (
+                Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING,
+                Capabilities::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING,
+            ),
+            (
+                Features::UNIFORM_BUFFER_AND_STORAGE_TEXTURE_ARRAY_NON_UNIFORM_INDEXING,
+                Capabilities::UNIFORM_BUFFER_AND_STORAGE_TEXTURE_ARRAY_NON_UNIFORM_INDEXING,
+            ),
+            (Features::MULTIVIEW, Capabilities::MULTIVIEW),
+            (
+                Features::SHADER_EARLY_DEPTH_TEST,
+                Capabilities::EARLY_DEPTH_TEST,
+            ),
+            (Features::RAY_QUERY, Capabilities::RAY_QUERY),
+            (
+                Features::DUAL_SOURCE_BLENDING,
+                Capabilities::DUAL_SOURCE_BLENDING,
+            ),
+        ];
+        let features = self.device().features();
+        optional
+            .into_iter()
+            .filter(|(feature, _)| !features.contains(*feature))
+            .fold(Capabilities::all(), |allowed, (_, capability)| {
+                allowed - capability
+            })
+    }
+
     fn select_adapter() -> Adapter {
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             dx12_shader_compiler: wgpu::util::dx12_shader_compiler_from_env().unwrap_or_default(),
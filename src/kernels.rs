@@ -0,0 +1,256 @@
+//! The kernels benchmarked in this repository, shared by the criterion benches
+//! and the `wgpu-bench` binary.
+pub mod layernorm;
+pub mod qgemm;
+pub mod rope;
+pub mod rope_cp;
+pub mod sgemm;
+
+use crate::{
+    catalogue, params, wgsl_template, KernelSource, Manifest, ManifestKernel, Params, Registry,
+    SearchSpace, Template,
+};
+
+/// Every kernel in this repository, with the parameters its bench uses by default.
+pub fn registry() -> Registry {
+    let gemm = params![
+        B = 1,
+        M = 1024,
+        N = 1024,
+        K = 1024,
+        TILE_DIM = 32,
+        ROW_PER_THREAD = 8
+    ];
+    let mut registry = Registry::default()
+        .register(
+            "sgemm",
+            "Tiled f32 matrix multiplication",
+            gemm.clone(),
+            sgemm::SGEMMBenchmark::from_params,
+        )
+        .register(
+            "qgemm",
+            "Tiled matrix multiplication with an int8 quantized B",
+            gemm,
+            qgemm::QGEMMBenchmark::from_params,
+        )
+        .register(
+            "rope",
+            "Rotary position embedding",
+            params![seq_len = 64],
+            |p| Ok(rope::Rope::new(p.get("seq_len")?)),
+        )
+        .register(
+            "rope_cp",
+            "Rotary position embedding over a partial rotary dimension",
+            params![],
+            |_| Ok(rope_cp::Rope {}),
+        );
+    for (name, template, workgroup_size) in layernorm::VARIANTS {
+        registry = registry.register(
+            format!("layernorm/{}", name),
+            "LayerNorm over the last dimension",
+            params![M = 2048, N = 512, eps = 1e-5],
+            move |p| layernorm::LayerNorm::from_params(template, workgroup_size, p),
+        );
+    }
+    let catalogue = catalogue();
+    for (path, e) in &catalogue.errors {
+        log::warn!("Skipping {}: {:#}", path.display(), e);
+    }
+    for manifest in &catalogue.manifests {
+        registry = registry.register(
+            &manifest.name,
+            &manifest.description,
+            manifest.defaults.clone(),
+            move |p| ManifestKernel::new(manifest, p),
+        );
+    }
+    registry
+}
+
+/// # TemplateSpace
+///
+/// A template in `kernels/` with the values of every Tera variable it reads, so that each
+/// combination can be rendered and validated without a GPU.
+pub struct TemplateSpace {
+    pub template: Template,
+    pub space: SearchSpace,
+    derived: fn(&Params) -> Params,
+}
+
+impl TemplateSpace {
+    pub fn new(template: Template, space: SearchSpace) -> Self {
+        Self {
+            template,
+            space,
+            derived: |_| Params::default(),
+        }
+    }
+
+    /// Sets variables computed from each configuration, e.g. the workgroup size of a tile.
+    pub fn derive(mut self, derived: fn(&Params) -> Params) -> Self {
+        self.derived = derived;
+        self
+    }
+
+    /// Renders every configuration the space allows.
+    pub fn render(&self) -> Vec<(Params, KernelSource)> {
+        self.space
+            .configs()
+            .into_iter()
+            .filter(|config| self.space.allows(config))
+            .map(|config| {
+                let config = config.merge(&(self.derived)(&config));
+                let source = KernelSource::render(self.template, &config.to_context());
+                (config, source)
+            })
+            .collect()
+    }
+}
+
+fn workgroup(x: usize, y: usize, z: usize) -> SearchSpace {
+    SearchSpace::default()
+        .axis("workgroup_size_x", [x])
+        .axis("workgroup_size_y", [y])
+        .axis("workgroup_size_z", [z])
+}
+
+//The tiled GEMMs run TILE_DIM / 4 x TILE_DIM / ROW_PER_THREAD invocations per workgroup
+fn gemm(fits: [&str; 3]) -> SearchSpace {
+    fits.iter()
+        .fold(SearchSpace::default(), |space, fit| {
+            space.axis(*fit, [false, true])
+        })
+        .axis("TILE_DIM", [16, 32, 64])
+        .axis("ROW_PER_THREAD", [2, 4, 8])
+        .constraint(|p| {
+            let (tile, rows) = (p.get::<usize>("TILE_DIM"), p.get::<usize>("ROW_PER_THREAD"));
+            tile.unwrap() % rows.unwrap() == 0
+        })
+}
+
+//Manifest kernels are rendered with their template variables at the default parameters
+fn manifest_space(manifest: &'static Manifest) -> anyhow::Result<TemplateSpace> {
+    let template_params = manifest.template_params(&manifest.defaults)?;
+    let workload = manifest.workload(&manifest.defaults.merge(&template_params))?;
+    let size = workload.size();
+    let space = template_params.iter().fold(
+        workgroup(size.0 as _, size.1 as _, size.2 as _),
+        |space, (key, value)| space.axis(key, [value]),
+    );
+    Ok(TemplateSpace::new(manifest.template(), space))
+}
+
+fn gemm_workgroup(p: &Params) -> Params {
+    let tile = p.get::<usize>("TILE_DIM").unwrap();
+    let rows = p.get::<usize>("ROW_PER_THREAD").unwrap();
+    params![
+        workgroup_size_x = tile / 4,
+        workgroup_size_y = tile / rows,
+        workgroup_size_z = 1
+    ]
+}
+
+/// Every template in `kernels/`, with the values its kernels render it with.
+pub fn templates() -> Vec<TemplateSpace> {
+    let sgemm_fits = ["FIT_A_OUTER", "FIT_B_OUTER", "FIT_INNER"];
+    let mut templates = vec![
+        TemplateSpace::new(
+            wgsl_template!("sgemm/gemm_scalar.wgsl"),
+            sgemm_fits
+                .iter()
+                .chain(&["TRANS_A", "TRANS_B"])
+                .fold(SearchSpace::default(), |space, flag| {
+                    space.axis(*flag, [false, true])
+                }),
+        ),
+        TemplateSpace::new(
+            wgsl_template!("sgemm/gemm_vectorized.wgsl"),
+            gemm(sgemm_fits),
+        )
+        .derive(gemm_workgroup),
+        TemplateSpace::new(wgsl_template!("sgemm/slow.wgsl"), SearchSpace::default()),
+        TemplateSpace::new(
+            wgsl_template!("qgemm/tfjs.wgsl"),
+            gemm(["A_FIT", "B_FIT", "OUT_FIT"]),
+        )
+        .derive(gemm_workgroup),
+        TemplateSpace::new(wgsl_template!("qgemm/tfjs2.wgsl"), gemm(sgemm_fits))
+            .derive(gemm_workgroup),
+        TemplateSpace::new(wgsl_template!("qgemm/slow.wgsl"), SearchSpace::default()),
+        TemplateSpace::new(wgsl_template!("rope/rope.wgsl"), workgroup(16, 8, 8)),
+        TemplateSpace::new(wgsl_template!("rope/rope_cp.wgsl"), workgroup(32, 1, 1)),
+        TemplateSpace::new(
+            wgsl_template!("roofline/bandwidth.wgsl"),
+            workgroup(256, 1, 1),
+        ),
+        TemplateSpace::new(wgsl_template!("roofline/flops.wgsl"), workgroup(256, 1, 1)),
+        TemplateSpace::new(
+            wgsl_template!("overhead/empty.wgsl"),
+            SearchSpace::default()
+                .axis("workgroup_size_x", [1, 64, 256])
+                .axis("workgroup_size_y", [1, 4])
+                .axis("workgroup_size_z", [1]),
+        ),
+    ];
+    for (_, template, workgroup_size) in layernorm::VARIANTS {
+        templates.push(TemplateSpace::new(
+            template,
+            workgroup(workgroup_size as _, 1, 1),
+        ));
+    }
+    for manifest in &catalogue().manifests {
+        match manifest_space(manifest) {
+            Ok(space) => templates.push(space),
+            Err(e) => panic!("{} has invalid defaults: {:#}", manifest.name, e),
+        }
+    }
+    templates
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn templates_validate() {
+        let mut failures = vec![];
+        for space in kernels::templates() {
+            let rendered = space.render();
+            assert!(
+                !rendered.is_empty(),
+                "{} has no configurations",
+                space.template.path
+            );
+            for (config, source) in rendered {
+                if let Err(e) = source.validate() {
+                    failures.push(format!("{} with {}:\n{:#}", space.template.path, config, e));
+                }
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+    }
+
+    #[test]
+    pub fn templates_declared() {
+        let declared = kernels::templates()
+            .iter()
+            .map(|t| t.template.path.to_string())
+            .collect::<Vec<_>>();
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        for dir in std::fs::read_dir(root.join("kernels")).unwrap() {
+            for file in std::fs::read_dir(dir.unwrap().path()).unwrap() {
+                let path = file.unwrap().path();
+                let path = path.strip_prefix(root).unwrap().to_string_lossy();
+                if path.ends_with(".wgsl") {
+                    assert!(
+                        declared.contains(&path.to_string()),
+                        "{} has no TemplateSpace",
+                        path
+                    );
+                }
+            }
+        }
+    }
+}
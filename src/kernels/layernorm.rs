@@ -0,0 +1,129 @@
+#![allow(non_snake_case)]
+use crate::{
+    dispatch_validate, layer_norm, op_metadata, params, shape, wgc, wgs, wgsl_template, CPUTensor,
+    Cost, GPUHandle, KernelBench, KernelContextExt, KernelSource, Params, Template, Workload,
+};
+
+op_metadata! {
+    #[derive(derive_new::new)]
+    pub struct LayerNormMeta {
+        M: u32,
+        N: u32,
+        ND4: u32,
+        eps: f32,
+    }
+}
+
+const WARP_SIZE: u32 = 32; //M1 warp size
+
+/// Every LayerNorm template in kernels/layernorm, with its name and workgroup size.
+pub const VARIANTS: [(&str, Template, u32); 6] = [
+    ("Naive", wgsl_template!("layernorm/naive_scalar.wgsl"), 128),
+    (
+        "NaiveVectorized",
+        wgsl_template!("layernorm/naive_vec4.wgsl"),
+        128,
+    ),
+    (
+        "NaiveOnePass",
+        wgsl_template!("layernorm/onepass_scalar.wgsl"),
+        128,
+    ),
+    (
+        "NaiveVectorizedOnePass",
+        wgsl_template!("layernorm/onepass_vec4.wgsl"),
+        128,
+    ),
+    (
+        "WelfordScalar",
+        wgsl_template!("layernorm/welford_scalar.wgsl"),
+        WARP_SIZE,
+    ),
+    (
+        "WelfordVectorized",
+        wgsl_template!("layernorm/welford_vec4.wgsl"),
+        WARP_SIZE,
+    ),
+];
+
+/// A LayerNorm kernel over an [M, N] input, one of the [`VARIANTS`].
+#[derive(derive_new::new, Debug)]
+pub struct LayerNorm {
+    template: Template,
+    workgroup_size: u32,
+    M: usize,
+    N: usize,
+    eps: f32,
+}
+
+impl LayerNorm {
+    pub fn from_params(
+        template: Template,
+        workgroup_size: u32,
+        p: &Params,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::new(
+            template,
+            workgroup_size,
+            p.get("M")?,
+            p.get("N")?,
+            p.get("eps")?,
+        ))
+    }
+}
+
+impl KernelBench for LayerNorm {
+    type Metadata = LayerNormMeta;
+
+    fn name() -> &'static str {
+        "LayerNorm"
+    }
+
+    fn source(&self, workload: &Workload) -> KernelSource {
+        let mut context = tera::Context::new();
+        context.insert_workload(workload);
+        KernelSource::render(self.template, &context)
+    }
+
+    fn tensors(&self) -> Vec<CPUTensor> {
+        let input = CPUTensor::randn::<f32>(shape![1, self.M, self.N]);
+        let scale = CPUTensor::randn::<f32>(shape![self.N]);
+        let bias = CPUTensor::randn::<f32>(shape![self.N]);
+        let output = CPUTensor::zeros::<f32>(shape![1, self.M, self.N]);
+        vec![input, scale, bias, output]
+    }
+
+    fn workload(&self, tensors: &[CPUTensor]) -> Workload {
+        let input = &tensors[0];
+        let [_B, M, _N] = input.shape().try_into().unwrap();
+        Workload::new(wgs![self.workgroup_size, 1, 1], wgc![M as _, 1, 1])
+    }
+
+    fn metadata(&self, tensors: &[CPUTensor]) -> Self::Metadata {
+        let input = &tensors[0];
+        let [_B, M, N] = input.shape().try_into().unwrap();
+        LayerNormMeta::new(M as _, N as _, (N / 4) as _, self.eps)
+    }
+
+    fn validate(&self, handle: &GPUHandle, tensors: &[CPUTensor]) -> anyhow::Result<()> {
+        let ground = layer_norm(&tensors[0], &tensors[1], &tensors[2], self.eps)?;
+        let mut gpu_tensors = dispatch_validate(handle, self, tensors);
+        let cpu_result = gpu_tensors.remove(3).into_cpu(handle)?;
+        ground.all_close(&cpu_result, 1e-5, 1e-5)
+    }
+
+    fn cost(&self, tensors: &[CPUTensor]) -> Cost {
+        let [_B, M, N] = tensors[0].shape().try_into().unwrap();
+        //mean (1), variance (3), normalise & affine (4)
+        Cost::from_tensors((8 * M * N) as u64, tensors)
+    }
+
+    fn params(&self) -> Params {
+        params![M = self.M, N = self.N, eps = self.eps]
+    }
+}
+
+/// Expected output of every variant, with PyTorch's default eps of 1e-5.
+pub fn ground_truth(tensors: &[CPUTensor]) -> anyhow::Result<CPUTensor> {
+    layer_norm(&tensors[0], &tensors[1], &tensors[2], 1e-5)
+}
@@ -0,0 +1,127 @@
+#![allow(non_snake_case)]
+use crate::{
+    dispatch_validate, op_metadata, params, qmatmul, shape, wgc, wgs, wgsl_template, CPUTensor,
+    Cost, GPUHandle, KernelBench, KernelContextExt, KernelSource, Params, Quantization, Quantizer,
+    Workload,
+};
+
+op_metadata! {
+    #[derive(derive_new::new)]
+    pub struct QGEMMMeta {
+        aShape: glam::IVec3,
+        aStrides: glam::IVec3,
+        bShape: glam::IVec3,
+        bStrides: glam::IVec3,
+        outShape: glam::IVec3,
+        outShapeStrides: glam::IVec3,
+        dimInner: i32,
+    }
+}
+
+#[derive(derive_new::new, Debug)]
+pub struct QGEMMBenchmark {
+    B: usize,
+    M: usize,
+    N: usize,
+    K: usize,
+    TILE_DIM: usize,
+    ROW_PER_THREAD: usize,
+}
+
+impl QGEMMBenchmark {
+    pub fn from_params(p: &Params) -> anyhow::Result<Self> {
+        Ok(Self::new(
+            p.get("B")?,
+            p.get("M")?,
+            p.get("N")?,
+            p.get("K")?,
+            p.get("TILE_DIM")?,
+            p.get("ROW_PER_THREAD")?,
+        ))
+    }
+
+    fn shape_fit(&self) -> [bool; 3] {
+        let aOuter = self.M;
+        let bOuter = self.N;
+        let dimInner = self.K;
+
+        let mut shape_fit = [false; 3];
+        shape_fit[0] = aOuter % self.TILE_DIM == 0;
+        shape_fit[1] = bOuter % self.TILE_DIM == 0;
+        shape_fit[2] = dimInner % self.TILE_DIM == 0;
+        shape_fit
+    }
+}
+
+impl KernelBench for QGEMMBenchmark {
+    type Metadata = QGEMMMeta;
+
+    fn name() -> &'static str {
+        "QGEMMBenchmark"
+    }
+
+    fn source(&self, workload: &Workload) -> KernelSource {
+        let mut context = tera::Context::new();
+        let shape_fit = self.shape_fit();
+        context.insert("A_FIT", &shape_fit[0]);
+        context.insert("B_FIT", &shape_fit[1]);
+        context.insert("INNER_FIT", &shape_fit[2]);
+
+        context.insert("TILE_DIM", &self.TILE_DIM);
+        context.insert("ROW_PER_THREAD", &self.ROW_PER_THREAD);
+        context.insert_workload(workload);
+        KernelSource::render(wgsl_template!("qgemm/tfjs.wgsl"), &context)
+    }
+
+    fn tensors(&self) -> Vec<CPUTensor> {
+        let (B, M, N, K) = (self.B, self.M, self.N, self.K);
+        let a = CPUTensor::randn::<f32>(shape![B, M, K]);
+        let b_unquant = CPUTensor::randn::<f32>(shape![B, K, N]);
+        let quantizer = Quantizer::new(Quantization::SInt8);
+        let quantized_b = quantizer.quantize(b_unquant.clone());
+        let output = CPUTensor::zeros::<f32>(shape![B, M, N]);
+        vec![a, quantized_b, output]
+    }
+
+    fn workload(&self, _: &[CPUTensor]) -> Workload {
+        let (TILE_DIM, ROW_PER_THREAD) = (self.TILE_DIM, self.ROW_PER_THREAD);
+        let workgroup_size = wgs![(TILE_DIM / 4) as _, (TILE_DIM / ROW_PER_THREAD) as _, 1];
+        let group_x = Workload::ceil(self.N, TILE_DIM);
+        let group_y = Workload::ceil(self.M, TILE_DIM);
+        let workgroup_count = wgc![group_x as _, group_y as _, self.B as u32];
+        Workload::new(workgroup_size, workgroup_count)
+    }
+
+    fn metadata(&self, _: &[CPUTensor]) -> Self::Metadata {
+        let (B, M, N, K) = (self.B as i32, self.M as i32, self.N as i32, self.K as i32);
+
+        let aShape = glam::IVec3::new(B, M, K);
+        let aStrides = glam::IVec3::new(M * K, K, 1);
+        let bShape = glam::IVec3::new(B, K, N);
+        let bStrides = glam::IVec3::new(K * N, N, 1);
+        let outShape = glam::IVec3::new(B, M, N);
+        let outStrides = glam::IVec3::new(M * N, N, 1);
+
+        let meta = QGEMMMeta::new(aShape, aStrides, bShape, bStrides, outShape, outStrides, K);
+        log::debug!("META: {:?}", meta);
+        meta
+    }
+
+    fn validate(&self, handle: &GPUHandle, tensors: &[CPUTensor]) -> anyhow::Result<()> {
+        let ground = qmatmul(&tensors[0], &tensors[1])?;
+        let mut gpu_tensors = dispatch_validate(handle, self, tensors);
+        let cpu_result = gpu_tensors.remove(2).into_cpu(handle)?;
+        log::debug!("OURS: {}", cpu_result);
+        log::debug!("GROUND: {}", ground);
+        ground.all_close(&cpu_result, 1e-2, 1e-2)
+    }
+
+    fn cost(&self, tensors: &[CPUTensor]) -> Cost {
+        let (B, M, N, K) = (self.B, self.M, self.N, self.K);
+        Cost::from_tensors((2 * B * M * N * K) as u64, tensors)
+    }
+
+    fn params(&self) -> Params {
+        params![B = self.B, M = self.M, N = self.N, K = self.K]
+    }
+}
@@ -0,0 +1,104 @@
+#![allow(non_snake_case)]
+use crate::{
+    dispatch_validate, op_metadata, params, rope, shape, wgc, wgs, wgsl_template, CPUTensor, Cost,
+    GPUHandle, KernelBench, KernelContextExt, KernelSource, Params, RopeStyle, Strides, Workload,
+};
+
+op_metadata! {
+    #[derive(derive_new::new)]
+    pub struct RopeMeta {
+        in_strides: glam::UVec3,
+        out_strides: glam::UVec3,
+        offset: u32,
+        base: f32,
+        scale: f32,
+    }
+}
+
+#[derive(derive_new::new, Debug)]
+pub struct Rope {
+    seq_len: usize,
+}
+
+impl KernelBench for Rope {
+    type Metadata = RopeMeta;
+
+    fn name() -> &'static str {
+        "RoPE"
+    }
+
+    fn source(&self, workload: &Workload) -> KernelSource {
+        let mut context = tera::Context::new();
+        context.insert_workload(workload);
+        KernelSource::render(wgsl_template!("rope/rope.wgsl"), &context)
+    }
+
+    // [batch_size, num_heads, seq_len, head_dim]
+    fn tensors(&self) -> Vec<CPUTensor> {
+        let input = CPUTensor::randn::<f32>(shape![2, 16, self.seq_len, 128]);
+        let output = CPUTensor::zeros::<f32>(shape![2, 16, self.seq_len, 128]);
+        vec![input, output]
+    }
+
+    fn workload(&self, tensors: &[CPUTensor]) -> Workload {
+        let input = &tensors[0];
+        let [_BS, _NH, SL, HD] = input.shape().try_into().unwrap();
+
+        let total_x = 128 / 2;
+        let total_y = SL;
+        let total_z = input.shape().numel() / (SL * HD);
+
+        let wgsx = 16;
+        let wgsy = 8;
+        let wgsz = 8;
+
+        let wgcx = total_x / wgsx;
+        let wgcy = total_y / wgsy;
+        let wgcz = total_z / wgsz;
+
+        Workload::new(
+            wgs![wgsx as _, wgsy as _, wgsz as _],
+            wgc![wgcx as _, wgcy as _, wgcz as _],
+        )
+    }
+
+    fn metadata(&self, tensors: &[CPUTensor]) -> Self::Metadata {
+        let input = &tensors[0];
+        let out = &tensors[1];
+        let mut input_shape = input.shape().clone();
+        let mut out_shape = out.shape().clone();
+        input_shape.remove(0);
+        out_shape.remove(0);
+        let in_strides = Strides::from(&input_shape);
+        let out_strides = Strides::from(&out_shape);
+        let meta = RopeMeta::new(
+            (&in_strides).into(),
+            (&out_strides).into(),
+            0,
+            f32::log2(10000.0),
+            1.0,
+        );
+        log::debug!("{:?}", meta);
+        meta
+    }
+
+    fn validate(&self, handle: &GPUHandle, tensors: &[CPUTensor]) -> anyhow::Result<()> {
+        //mlx's nn.RoPE(128)
+        let ground = rope(&tensors[0], RopeStyle::HalfSplit, 128, 10000.0, 0)?;
+        let mut gpu_tensors = dispatch_validate(handle, self, tensors);
+        let cpu_result = gpu_tensors.remove(1).into_cpu(handle)?;
+        log::debug!("GROUND: {}", ground);
+        log::debug!("US: {}", cpu_result);
+        ground.all_close(&cpu_result, 1e-5, 1e-5)
+    }
+
+    fn cost(&self, tensors: &[CPUTensor]) -> Cost {
+        //Rotating a pair takes 4 multiplies and 2 adds, trigonometry not counted
+        let numel = tensors[0].shape().numel();
+        Cost::from_tensors(3 * numel as u64, tensors)
+    }
+
+    fn params(&self) -> Params {
+        params![seq_len = self.seq_len]
+    }
+}
@@ -0,0 +1,85 @@
+#![allow(non_snake_case)]
+use crate::{
+    dispatch_validate, op_metadata, rope, shape, wgc, wgs, wgsl_template, CPUTensor, Cost,
+    GPUHandle, KernelBench, KernelContextExt, KernelSource, RopeStyle, Strides, Workload,
+};
+
+op_metadata! {
+    #[derive(derive_new::new)]
+    pub struct RopeMeta {
+        in_strides: glam::UVec4,
+        out_strides: glam::UVec4,
+        offset: u32,
+        base: f32,
+        rotary_dim: u32,
+        head_dim: u32,
+    }
+}
+
+#[derive(Debug)]
+pub struct Rope {}
+
+impl KernelBench for Rope {
+    type Metadata = RopeMeta;
+
+    fn name() -> &'static str {
+        "RoPE"
+    }
+
+    fn source(&self, workload: &Workload) -> KernelSource {
+        let mut context = tera::Context::new();
+        context.insert_workload(workload);
+        KernelSource::render(wgsl_template!("rope/rope_cp.wgsl"), &context)
+    }
+
+    // [ batch_size, num_heads, seq_len, head_dim ]
+    fn tensors(&self) -> Vec<CPUTensor> {
+        let input = CPUTensor::randn::<f32>(shape![1, 16, 64, 128]);
+        let output = CPUTensor::zeros::<f32>(shape![1, 16, 64, 128]);
+        vec![input, output]
+    }
+
+    //  rotary_ndims = int(args.rotary_pct * head_dim)
+    //    threads_per_block = (rotary_ndims, )
+    //    blocks_per_grid = (batch_size, n_heads, seq_len)
+    fn workload(&self, tensors: &[CPUTensor]) -> Workload {
+        let input = &tensors[0];
+        let [BS, NH, SL, _HD] = input.shape().try_into().unwrap();
+        Workload::new(wgs![32, 1, 1], wgc![BS as _, NH as _, SL as _])
+    }
+
+    fn metadata(&self, tensors: &[CPUTensor]) -> Self::Metadata {
+        let input = &tensors[0];
+        let out = &tensors[1];
+        let input_shape = input.shape().clone();
+        let out_shape = out.shape().clone();
+        let in_strides = Strides::from(&input_shape);
+        let out_strides = Strides::from(&out_shape);
+        let head_dim = input_shape[3] as u32;
+        let meta = RopeMeta::new(
+            (&in_strides).into(),
+            (&out_strides).into(),
+            0,
+            10000.0,
+            32,
+            head_dim,
+        );
+        log::debug!("{:?}", meta);
+        meta
+    }
+
+    fn validate(&self, handle: &GPUHandle, tensors: &[CPUTensor]) -> anyhow::Result<()> {
+        let ground = rope(&tensors[0], RopeStyle::Interleaved, 32, 10000.0, 0)?;
+        let mut gpu_tensors = dispatch_validate(handle, self, tensors);
+        let cpu_result = gpu_tensors.remove(1).into_cpu(handle)?;
+        log::debug!("GROUND: {}", ground);
+        log::debug!("US: {}", cpu_result);
+        ground.all_close(&cpu_result, 1e-5, 1e-5)
+    }
+
+    fn cost(&self, tensors: &[CPUTensor]) -> Cost {
+        //Rotating a pair takes 4 multiplies and 2 adds, trigonometry not counted
+        let numel = tensors[0].shape().numel();
+        Cost::from_tensors(3 * numel as u64, tensors)
+    }
+}
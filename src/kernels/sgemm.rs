@@ -0,0 +1,160 @@
+#![allow(non_snake_case)]
+use crate::{
+    dispatch_validate, matmul, op_metadata, params, shape, wgc, wgs, wgsl_template, CPUTensor,
+    Cost, GPUHandle, KernelBench, KernelContextExt, KernelSource, Params, Workload,
+};
+
+op_metadata! {
+    pub struct SGEMMMeta {
+        aShape: glam::IVec3,
+        aStrides: glam::IVec3,
+        bShape: glam::IVec3,
+        bStrides: glam::IVec3,
+        outShape: glam::IVec3,
+        outStrides: glam::IVec3,
+        dimAOuter: i32,
+        dimBOuter: i32,
+        dimInner: i32,
+    }
+}
+
+#[derive(derive_new::new, Debug)]
+pub struct SGEMMBenchmark {
+    B: usize,
+    M: usize,
+    N: usize,
+    K: usize,
+    TILE_DIM: usize,
+    ROW_PER_THREAD: usize,
+    trans_a: bool,
+    trans_b: bool,
+}
+
+impl SGEMMBenchmark {
+    //Untransposed problem from B, M, N, K and a TILE_DIM, ROW_PER_THREAD configuration
+    pub fn from_params(p: &Params) -> anyhow::Result<Self> {
+        Ok(Self::new(
+            p.get("B")?,
+            p.get("M")?,
+            p.get("N")?,
+            p.get("K")?,
+            p.get("TILE_DIM")?,
+            p.get("ROW_PER_THREAD")?,
+            false,
+            false,
+        ))
+    }
+
+    fn shape_fit(&self) -> [bool; 3] {
+        let aOuter = if self.trans_a { self.K } else { self.M };
+        let bOuter = if self.trans_b { self.K } else { self.N };
+        let dimInner = if self.trans_a { self.M } else { self.K };
+
+        let mut shape_fit = [false; 3];
+        shape_fit[0] = aOuter % self.TILE_DIM == 0;
+        shape_fit[1] = bOuter % self.TILE_DIM == 0;
+        shape_fit[2] = dimInner % self.TILE_DIM == 0;
+        log::debug!("SHAPE FIT: {:?}", shape_fit);
+        shape_fit
+    }
+}
+
+impl KernelBench for SGEMMBenchmark {
+    type Metadata = SGEMMMeta;
+
+    fn name() -> &'static str {
+        "SGEMMBenchmark"
+    }
+
+    fn source(&self, workload: &Workload) -> KernelSource {
+        let mut context = tera::Context::new();
+
+        let is_vec4 = !self.trans_a
+            && !self.trans_b
+            && (self.M % 4 == 0)
+            && (self.N % 4 == 0)
+            && (self.K % 4 == 0);
+        let template = if is_vec4 {
+            wgsl_template!("sgemm/gemm_vectorized.wgsl")
+        } else {
+            wgsl_template!("sgemm/gemm_scalar.wgsl")
+        };
+        let shape_fit = self.shape_fit();
+        context.insert("FIT_A_OUTER", &shape_fit[0]);
+        context.insert("FIT_B_OUTER", &shape_fit[1]);
+        context.insert("FIT_INNER", &shape_fit[2]);
+        context.insert("TRANS_A", &self.trans_a);
+        context.insert("TRANS_B", &self.trans_b);
+
+        context.insert("TILE_DIM", &self.TILE_DIM);
+        context.insert("ROW_PER_THREAD", &self.ROW_PER_THREAD);
+        context.insert_workload(workload);
+        KernelSource::render(template, &context)
+    }
+
+    fn tensors(&self) -> Vec<CPUTensor> {
+        let (B, M, N, K) = (self.B, self.M, self.N, self.K);
+        let a = CPUTensor::randn::<f32>(shape![B, M, K]);
+        let b = CPUTensor::randn::<f32>(shape![B, K, N]);
+        let output = CPUTensor::zeros::<f32>(shape![B, M, N]);
+        vec![a, b, output]
+    }
+
+    fn workload(&self, _: &[CPUTensor]) -> Workload {
+        let (TILE_DIM, ROW_PER_THREAD) = (self.TILE_DIM, self.ROW_PER_THREAD);
+        let workgroup_size = wgs![(TILE_DIM / 4) as _, (TILE_DIM / ROW_PER_THREAD) as _, 1];
+        let dimA = if self.trans_a { self.K } else { self.M };
+        let dimB = if self.trans_b { self.K } else { self.N };
+        let group_x = Workload::ceil(dimB, TILE_DIM);
+        let group_y = Workload::ceil(dimA, TILE_DIM);
+        let workgroup_count = wgc![group_x as _, group_y as _, self.B as u32];
+        Workload::new(workgroup_size, workgroup_count)
+    }
+
+    fn metadata(&self, _: &[CPUTensor]) -> Self::Metadata {
+        let (B, M, N, K) = (self.B as i32, self.M as i32, self.N as i32, self.K as i32);
+
+        let aShape = glam::IVec3::new(B, M, K);
+        let aStrides = glam::IVec3::new(M * K, K, 1);
+        let bShape = glam::IVec3::new(B, K, N);
+        let bStrides = glam::IVec3::new(K * N, N, 1);
+        let outShape = glam::IVec3::new(B, M, N);
+        let outStrides = glam::IVec3::new(M * N, N, 1);
+
+        let dimAOuter = if self.trans_a { K } else { M };
+        let dimBOuter = if self.trans_b { K } else { N };
+        let dimInner = if self.trans_a { M } else { K };
+
+        let meta = SGEMMMeta {
+            aShape,
+            aStrides,
+            bShape,
+            bStrides,
+            outShape,
+            outStrides,
+            dimAOuter,
+            dimBOuter,
+            dimInner,
+        };
+        log::debug!("META: {:?}", meta);
+        meta
+    }
+
+    fn validate(&self, handle: &GPUHandle, tensors: &[CPUTensor]) -> anyhow::Result<()> {
+        let ground = matmul(&tensors[0], &tensors[1], self.trans_a, self.trans_b)?;
+        let mut gpu_tensors = dispatch_validate(handle, self, tensors);
+        let cpu_result = gpu_tensors.remove(2).into_cpu(handle)?;
+        log::debug!("GROUND: {}", ground);
+        log::debug!("OURS: {}", cpu_result);
+        ground.all_close(&cpu_result, 1e-5, 1e-5)
+    }
+
+    fn cost(&self, tensors: &[CPUTensor]) -> Cost {
+        let (B, M, N, K) = (self.B, self.M, self.N, self.K);
+        Cost::from_tensors((2 * B * M * N * K) as u64, tensors)
+    }
+
+    fn params(&self) -> Params {
+        params![B = self.B, M = self.M, N = self.N, K = self.K]
+    }
+}
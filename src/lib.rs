@@ -1,28 +1,82 @@
 #![feature(int_roundings)]
+mod accuracy;
+mod artefacts;
+mod autotune;
 mod bench;
+mod checkpoint;
+mod compare;
 mod data;
 mod dtype;
+mod export;
+mod expr;
+mod golden;
 mod handle;
+pub mod kernels;
+mod manifest;
 mod metadata;
+mod npy;
+mod params;
+mod portability;
 mod quant;
+mod query;
+mod record;
+mod reference;
+mod reflect;
+mod registry;
+mod regression;
+mod roofline;
 mod shape;
+mod source;
+mod stats;
 mod storage;
 mod strides;
 mod tensor;
+#[cfg(test)]
+mod testing;
+mod throughput;
+mod wallclock;
 mod workload;
 
-use std::{cell::Cell, ops::Range};
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    ops::Range,
+    time::Instant,
+};
 
+pub use accuracy::*;
+pub use artefacts::*;
+pub use autotune::*;
 pub use bench::*;
+pub use checkpoint::*;
+pub use compare::*;
 pub use data::*;
 pub use dtype::*;
+pub use export::*;
+pub use expr::*;
+pub use golden::*;
 pub use handle::*;
+pub use manifest::*;
 pub use metadata::*;
+pub use params::*;
+pub use portability::*;
 pub use quant::*;
+pub use query::*;
+pub use record::*;
+pub use reference::*;
+pub use reflect::*;
+pub use registry::*;
+pub use regression::*;
+pub use roofline::*;
 pub use shape::*;
+pub use source::*;
+pub use stats::*;
 pub use storage::*;
 pub use strides::*;
 pub use tensor::*;
+#[cfg(test)]
+pub(crate) use testing::*;
+pub use throughput::*;
+pub use wallclock::*;
 pub use workload::*;
 
 use criterion::{
@@ -31,6 +85,7 @@ use criterion::{
 };
 use wgpu::QuerySet;
 
+/// Number of queries in each query set of the [`QueryPool`].
 pub const MAX_QUERIES: u32 = 4096;
 
 /// Start and end index in the counter sample buffer
@@ -64,12 +119,33 @@ impl From<QueryPair> for Range<u32> {
     }
 }
 
+/// How the dispatches of a sample are timed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimingMode {
+    /// One pair of timestamps around the pass, averaged over its dispatches.
+    #[default]
+    Averaged,
+    /// A pair of timestamps around every dispatch, to report their distribution.
+    /// Uses `TIMESTAMP_QUERY_INSIDE_PASSES` when available, otherwise one pass per dispatch.
+    PerDispatch,
+}
+
+/// # WgpuTimer
+///
+/// Times kernels with GPU timestamps when the adapter supports `TIMESTAMP_QUERY`,
+/// otherwise falls back to a [`WallClockTimer`].
 pub struct WgpuTimer {
     handle: GPUHandle,
-    query_set: QuerySet,
-    resolve_buffer: wgpu::Buffer,
-    destination_buffer: wgpu::Buffer,
-    current_query: Cell<QueryPair>,
+    mode: TimingMode,
+    queries: Option<QueryPool>,
+    dispatches: Cell<u32>,
+    iterations: Cell<u64>, //Iterations of `dispatch` since the measurement started
+    samples: RefCell<Vec<f64>>, //Nanoseconds per dispatch of every measurement
+    peaks: OnceCell<Option<DevicePeaks>>,
+    overhead: Cell<Option<f64>>, //Nanoseconds per dispatch of an empty kernel
+    subtract_overhead: Cell<bool>,
+    wall_clock: WallClockTimer,
+    formatter: WgpuTimerFormatter,
 }
 
 //TODO: dumb
@@ -77,133 +153,234 @@ unsafe impl Send for WgpuTimer {}
 unsafe impl Sync for WgpuTimer {}
 
 impl WgpuTimer {
+    /// Default number of dispatches per timed sample.
     pub const COMPUTE_PER_QUERY: u64 = 100;
+    /// Set to subtract the empty-kernel overhead from every measurement.
+    pub const SUBTRACT_OVERHEAD_VAR: &'static str = "WGPU_BENCH_SUBTRACT_OVERHEAD";
 
     pub fn new(handle: GPUHandle) -> Self {
-        let query_set = handle.device().create_query_set(&wgpu::QuerySetDescriptor {
-            count: MAX_QUERIES,
-            ty: wgpu::QueryType::Timestamp,
-            label: None,
-        });
-
-        let size = MAX_QUERIES as u64 * std::mem::size_of::<u64>() as u64;
-
-        let resolve_buffer = handle.device().create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size,
-            usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::QUERY_RESOLVE,
-            mapped_at_creation: false,
-        });
-
-        let destination_buffer = handle.device().create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size,
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-            mapped_at_creation: false,
-        });
+        Self::with_mode(handle, TimingMode::default())
+    }
+
+    pub fn with_mode(handle: GPUHandle, mode: TimingMode) -> Self {
+        let queries = handle
+            .supports_timestamps()
+            .then(|| QueryPool::new(handle.clone()));
+        let clock = if queries.is_some() {
+            Clock::Timestamp
+        } else {
+            Clock::WallClock
+        };
+        let mode = if clock == Clock::WallClock && mode == TimingMode::PerDispatch {
+            log::warn!("Per-dispatch timing requires TIMESTAMP_QUERY, averaging instead");
+            TimingMode::Averaged
+        } else {
+            mode
+        };
 
         Self {
             handle,
-            query_set,
-            resolve_buffer,
-            destination_buffer,
-            current_query: QueryPair::first().into(),
+            mode,
+            queries,
+            dispatches: Cell::new(Self::COMPUTE_PER_QUERY as _),
+            iterations: Cell::new(0),
+            samples: RefCell::new(vec![]),
+            peaks: OnceCell::new(),
+            overhead: Cell::new(None),
+            subtract_overhead: Cell::new(std::env::var_os(Self::SUBTRACT_OVERHEAD_VAR).is_some()),
+            wall_clock: WallClockTimer::default(),
+            formatter: WgpuTimerFormatter::new(clock),
         }
     }
 
-    pub fn resolve_pass(&self, encoder: &mut wgpu::CommandEncoder, pass_query: QueryPair) {
-        let resolution_range = pass_query.into();
-        log::trace!("Resolution range: {:?}", resolution_range);
-        encoder.resolve_query_set(&self.query_set, resolution_range, &self.resolve_buffer, 0);
-        let size = pass_query.size();
-        log::trace!("Resolution size in bytes: {:?}", size);
-        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.destination_buffer, 0, size);
-    }
-
     pub fn handle(&self) -> &GPUHandle {
         &self.handle
     }
 
-    pub fn query_set(&self) -> &QuerySet {
-        &self.query_set
+    /// The clock this timer measures with.
+    pub fn clock(&self) -> Clock {
+        self.formatter.clock()
+    }
+
+    pub fn mode(&self) -> TimingMode {
+        self.mode
+    }
+
+    /// Number of dispatches recorded per timed sample.
+    /// `dispatch` records this many, and the measurement divides by it.
+    pub fn dispatches(&self) -> u32 {
+        self.dispatches.get()
+    }
+
+    /// Sets the cost of the kernel being benchmarked, see [`WgpuTimerFormatter::set_cost`].
+    pub fn set_cost(&self, cost: Cost) {
+        self.formatter.set_cost(cost);
+    }
+
+    pub fn set_dispatches(&self, dispatches: u32) {
+        assert!(
+            dispatches > 0,
+            "At least one dispatch per sample is required"
+        );
+        self.dispatches.set(dispatches);
+    }
+
+    /// Called by `dispatch` once per iteration, so that measurements can be
+    /// converted to nanoseconds per dispatch.
+    pub fn record_iteration(&self) {
+        self.iterations.set(self.iterations.get() + 1);
+    }
+
+    /// Nanoseconds per dispatch of every measurement since `reset_samples`.
+    pub fn samples(&self) -> Vec<f64> {
+        self.samples.borrow().clone()
+    }
+
+    pub fn reset_samples(&self) {
+        self.samples.borrow_mut().clear();
+    }
+
+    /// Peaks of the device for roofline analysis, see [`DevicePeaks::resolve`].
+    /// Resolved on first use, which may run the peak measurement kernels.
+    pub fn peaks(&self) -> Option<DevicePeaks> {
+        *self.peaks.get_or_init(|| DevicePeaks::resolve(self))
+    }
+
+    /// Sets the peaks used for roofline analysis, if they have not been resolved yet.
+    pub fn set_peaks(&self, peaks: DevicePeaks) {
+        let _ = self.peaks.set(Some(peaks));
+    }
+
+    /// Overhead of an empty dispatch with the current kernel's geometry, in nanoseconds.
+    pub fn overhead(&self) -> Option<f64> {
+        self.overhead.get()
+    }
+
+    pub fn set_overhead(&self, overhead: Option<f64>) {
+        self.overhead.set(overhead);
+        self.formatter.set_corrected(self.corrected());
+    }
+
+    /// Whether the overhead is subtracted from measurements once it is known.
+    pub fn subtract_overhead(&self) -> bool {
+        self.subtract_overhead.get()
+    }
+
+    pub fn set_subtract_overhead(&self, subtract: bool) {
+        self.subtract_overhead.set(subtract);
+        self.formatter.set_corrected(self.corrected());
+    }
+
+    /// How measurements are labelled, see [`WgpuTimerFormatter::label`].
+    pub fn label(&self) -> String {
+        self.formatter.label()
+    }
+
+    /// Whether measurements currently have the overhead subtracted.
+    pub fn corrected(&self) -> bool {
+        self.subtract_overhead() && self.overhead().is_some()
+    }
+
+    //Inverse of `to_f64`
+    fn from_ns(&self, ns: f64) -> u64 {
+        match self.clock() {
+            Clock::Timestamp => (ns / self.handle.queue().get_timestamp_period() as f64) as u64,
+            Clock::WallClock => ns as u64,
+        }
+    }
+
+    /// The query pool, if every dispatch should be timed separately.
+    pub fn per_dispatch_queries(&self) -> Option<&QueryPool> {
+        match self.mode {
+            TimingMode::PerDispatch => self.queries.as_ref(),
+            TimingMode::Averaged => None,
+        }
+    }
+
+    /// Discards the per-dispatch durations recorded so far.
+    pub fn reset_dispatch_stats(&self) {
+        if let Some(queries) = self.per_dispatch_queries() {
+            queries.record_samples();
+        }
+    }
+
+    /// Distribution of the dispatches timed since `reset_dispatch_stats`.
+    /// Only available in [`TimingMode::PerDispatch`].
+    pub fn dispatch_stats(&self) -> Option<DispatchStats> {
+        let samples = self.per_dispatch_queries()?.samples()?;
+        let period = self.handle.queue().get_timestamp_period() as f64;
+        let to_ns = |ticks: &Vec<u64>| ticks.iter().map(|t| *t as f64 * period).collect::<Vec<_>>();
+        DispatchStats::new(&to_ns(&samples.durations), &to_ns(&samples.first))
+    }
+
+    pub fn query_set(&self) -> Option<&QuerySet> {
+        self.queries.as_ref().map(QueryPool::query_set)
     }
 
     pub fn increment_query(&self) {
-        let pair = self.current_query.get();
-        if pair.end + 2 >= MAX_QUERIES {
-            panic!("Number of queries exceeds MAX_QUERIES, reduce duration of benchmark");
+        if let Some(queries) = &self.queries {
+            queries.increment_query();
         }
-        self.current_query.set(QueryPair {
-            start: pair.start + 2,
-            end: pair.end + 2,
-        });
     }
 
     pub fn current_query(&self) -> QueryPair {
-        self.current_query.get()
+        self.queries
+            .as_ref()
+            .map_or(QueryPair::first(), QueryPool::current_query)
     }
 
     //Fetches the current query as ComputePassTimestampWrites
-    pub fn timestamp_writes(&self) -> wgpu::ComputePassTimestampWrites {
-        wgpu::ComputePassTimestampWrites {
-            query_set: &self.query_set,
-            beginning_of_pass_write_index: Some(self.current_query().start),
-            end_of_pass_write_index: Some(self.current_query().end),
-        }
+    //None when timing with the wall-clock
+    pub fn timestamp_writes(&self) -> Option<wgpu::ComputePassTimestampWrites> {
+        self.queries.as_ref().map(QueryPool::timestamp_writes)
     }
 
     pub fn hardware_elapsed(&self, timestamps: &[u64]) -> u64 {
-        assert!(timestamps.len() % 2 == 0);
-        let mut elapsed = 0;
-        for i in (0..timestamps.len()).step_by(2) {
-            elapsed += timestamps[i + 1] - timestamps[i];
-        }
-        elapsed
+        QueryPool::hardware_elapsed(timestamps)
     }
 }
 
 impl Measurement for &WgpuTimer {
-    type Intermediate = u32; // Index of the start query
+    type Intermediate = Option<Instant>; // Host start time, only set for the wall-clock
 
     type Value = u64; // Raw unscaled GPU counter
                       // Must be multiplied by the timestamp period to get nanoseconds
+                      // Nanoseconds when timing with the wall-clock
 
     fn start(&self) -> Self::Intermediate {
+        let Some(queries) = &self.queries else {
+            self.iterations.set(0);
+            return Some((&self.wall_clock).start());
+        };
+        //Discard anything recorded outside of the measurement
+        self.iterations.set(0);
+        queries.drain();
         log::trace!("\nQuery at start of pass: {:?}", self.current_query());
-        0
+        None
     }
 
-    fn end(&self, start_index: Self::Intermediate) -> Self::Value {
-        log::trace!("\nQuery at end of pass: {:?}", self.current_query());
-        let mut encoder = self
-            .handle
-            .device()
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-
-        //Large window, eg 0..512
-        let pass_query = QueryPair {
-            start: start_index,
-            end: self.current_query().end - 2, //decrement here to counteract last iter
+    fn end(&self, start: Self::Intermediate) -> Self::Value {
+        let value = match &self.queries {
+            Some(queries) => {
+                log::trace!("\nQuery at end of pass: {:?}", self.current_query());
+                queries.drain() / self.dispatches() as u64
+            }
+            None => {
+                let start = start.expect("Wall-clock measurement requires a start time");
+                (&self.wall_clock).end(start) / self.dispatches() as u64
+            }
         };
-        log::trace!("Pass range: {:?}", pass_query);
-
-        self.resolve_pass(&mut encoder, pass_query);
-        self.handle().queue().submit(Some(encoder.finish()));
-        self.handle.device().poll(wgpu::Maintain::Wait);
-
-        self.destination_buffer
-            .slice(..)
-            .map_async(wgpu::MapMode::Read, |_| ());
-        self.handle.device().poll(wgpu::Maintain::Wait);
-        let timestamps: Vec<u64> = {
-            let byte_range = pass_query.start_address()..pass_query.end_address();
-            let timestamp_view = self.destination_buffer.slice(byte_range).get_mapped_range();
-            (*bytemuck::cast_slice(&timestamp_view)).to_vec()
+        let iterations = self.iterations.replace(0).max(1);
+        let value = match self.overhead() {
+            Some(overhead) if self.subtract_overhead() => {
+                value.saturating_sub(self.from_ns(overhead) * iterations)
+            }
+            _ => value,
         };
-        log::trace!("Timestamps: {:?}", timestamps);
-        self.destination_buffer.unmap();
-        self.current_query.set(QueryPair::first());
-        self.hardware_elapsed(&timestamps) / WgpuTimer::COMPUTE_PER_QUERY
+        self.samples
+            .borrow_mut()
+            .push(self.to_f64(&value) / iterations as f64);
+        value
     }
 
     fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
@@ -215,53 +392,92 @@ impl Measurement for &WgpuTimer {
     }
 
     fn to_f64(&self, value: &Self::Value) -> f64 {
-        (self.handle.queue().get_timestamp_period() as f64) * (*value as f64)
+        match self.clock() {
+            Clock::Timestamp => {
+                (self.handle.queue().get_timestamp_period() as f64) * (*value as f64)
+            }
+            Clock::WallClock => (&self.wall_clock).to_f64(value),
+        }
     }
 
     fn formatter(&self) -> &dyn ValueFormatter {
-        &WgpuTimerFormatter
+        &self.formatter
     }
 }
 
-struct WgpuTimerFormatter;
+#[derive(Debug)]
+pub struct WgpuTimerFormatter {
+    clock: Clock,
+    cost: Cell<Option<Cost>>,
+    corrected: Cell<bool>, //Empty-kernel overhead subtracted
+}
+
+impl WgpuTimerFormatter {
+    pub fn new(clock: Clock) -> Self {
+        Self {
+            clock,
+            cost: Cell::new(None),
+            corrected: Cell::new(false),
+        }
+    }
+
+    pub fn clock(&self) -> Clock {
+        self.clock
+    }
+
+    /// Sets the cost of the kernel being benchmarked.
+    /// Throughput is then reported as FLOP/s, B/s and arithmetic intensity together.
+    pub fn set_cost(&self, cost: Cost) {
+        self.cost.set(Some(cost));
+    }
+
+    /// Marks values as having the empty-kernel overhead subtracted, rather than raw.
+    pub fn set_corrected(&self, corrected: bool) {
+        self.corrected.set(corrected);
+    }
+
+    /// Describes how values were obtained, e.g. `raw` or `wall-clock, overhead subtracted`.
+    pub fn label(&self) -> String {
+        let correction = match self.corrected.get() {
+            true => "overhead subtracted",
+            false => "raw",
+        };
+        match self.clock {
+            Clock::Timestamp => correction.to_string(),
+            Clock::WallClock => format!("wall-clock, {}", correction),
+        }
+    }
+}
 
 impl ValueFormatter for WgpuTimerFormatter {
     fn format_value(&self, value: f64) -> String {
-        format!("{:.4} ns", value)
+        format!("{:.4} ns ({})", value, self.label())
     }
 
     fn format_throughput(&self, throughput: &Throughput, value: f64) -> String {
-        match throughput {
-            Throughput::Bytes(b) => format!(
-                "{:.4} GiB/s",
-                (*b as f64) / (1024.0 * 1024.0 * 1024.0) / (value * 1e-9)
-            ),
-            Throughput::Elements(e) => {
-                let gflop = (*e as f64) / 1e9;
-                let seconds = value * 1e-9;
-                let gigaflop_per_second = gflop / seconds;
-                format!("{:.4} GFLOP/s", gigaflop_per_second)
-            }
-            _ => unreachable!(),
-        }
+        let cost = self.cost.get().unwrap_or_else(|| throughput.into());
+        cost.format_rates(value)
     }
 
     fn scale_values(&self, _typical_value: f64, _values: &mut [f64]) -> &'static str {
         "ns"
     }
 
-    /// TODO!
     fn scale_throughputs(
         &self,
-        _typical_value: f64,
+        typical_value: f64,
         throughput: &Throughput,
-        _values: &mut [f64],
+        values: &mut [f64],
     ) -> &'static str {
-        match throughput {
-            Throughput::Bytes(_) => "GiB/s",
-            Throughput::Elements(_) => "elements/s",
-            _ => unreachable!(),
+        let (quantity, units) = match throughput {
+            Throughput::Bytes(b) | Throughput::BytesDecimal(b) => (*b as f64, &BYTE_UNITS),
+            Throughput::Elements(e) => (*e as f64, &FLOP_UNITS),
+        };
+        let (denominator, unit) = scale_si(quantity * 1e9 / typical_value, units);
+        for value in values {
+            *value = quantity * 1e9 / *value / denominator;
         }
+        unit
     }
 
     fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
@@ -286,4 +502,24 @@ mod tests {
         let query = QueryPair::first();
         assert_eq!(query.size(), 16);
     }
+
+    #[test]
+    pub fn elapsed_sums_pairs() {
+        let timestamps = [10, 15, 100, 130, 131, 132];
+        assert_eq!(QueryPool::hardware_elapsed(&timestamps), 36);
+    }
+
+    #[test]
+    pub fn value_labels() {
+        let formatter = WgpuTimerFormatter::new(Clock::Timestamp);
+        assert_eq!(formatter.format_value(1.5), "1.5000 ns (raw)");
+        formatter.set_corrected(true);
+        assert_eq!(
+            formatter.format_value(1.5),
+            "1.5000 ns (overhead subtracted)"
+        );
+
+        let formatter = WgpuTimerFormatter::new(Clock::WallClock);
+        assert_eq!(formatter.format_value(2.0), "2.0000 ns (wall-clock, raw)");
+    }
 }
@@ -0,0 +1,703 @@
+use std::path::{Path, PathBuf};
+
+use numpy::PyArrayDyn;
+use pyo3::{types::PyDict, Python};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::Deserialize;
+
+use crate::{
+    dispatch_validate, reflect_metadata, CPUTensor, Cost, DType, Expr, GPUHandle, Golden,
+    KernelBench, KernelContextExt, KernelSource, NamedTensors, OpMetadata, Params, Shape,
+    StructLayout, Template, WorkgroupCount, WorkgroupSize, Workload,
+};
+
+fn dtype<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<DType, D::Error> {
+    let name = <String as serde::Deserialize>::deserialize(deserializer)?;
+    name.parse().map_err(serde::de::Error::custom)
+}
+
+fn default_dtype() -> DType {
+    DType::F32
+}
+
+fn default_tolerance() -> f32 {
+    1e-5
+}
+
+//Plain TOML values as parameters, e.g. `N = 1024` as N=1024
+fn to_params(table: &toml::Table) -> anyhow::Result<Params> {
+    let mut params = Params::default();
+    for (key, value) in table {
+        match value {
+            toml::Value::String(s) => params.insert(key, s),
+            toml::Value::Integer(i) => params.insert(key, i),
+            toml::Value::Float(f) => params.insert(key, f),
+            toml::Value::Boolean(b) => params.insert(key, b),
+            _ => anyhow::bail!("Parameter {} must be a string, number or bool", key),
+        }
+    }
+    Ok(params)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Init {
+    #[default]
+    Randn,
+    Zeros,
+}
+
+/// A tensor bound to the kernel, in the order of its `[[inputs]]` and then `[[outputs]]`.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TensorSpec {
+    pub name: String,
+    pub shape: Vec<Expr>,
+    #[serde(default = "default_dtype", deserialize_with = "dtype")]
+    pub dtype: DType,
+    /// Outputs always start as zeros.
+    #[serde(default)]
+    pub init: Init,
+}
+
+impl TensorSpec {
+    pub fn shape(&self, params: &Params) -> anyhow::Result<Shape> {
+        let dims = self
+            .shape
+            .iter()
+            .map(|d| d.eval_usize(params))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(|e| anyhow::anyhow!("Shape of {}: {:#}", self.name, e))?;
+        Ok(Shape::from(dims.as_slice()))
+    }
+
+    fn tensor(&self, shape: Shape, init: Init, rng: &mut impl Rng) -> anyhow::Result<CPUTensor> {
+        Ok(match (init, self.dtype) {
+            (Init::Randn, DType::F32) => CPUTensor::randn_from::<f32>(shape, rng),
+            (Init::Zeros, DType::F32) => CPUTensor::zeros::<f32>(shape),
+            (Init::Zeros, DType::F16) => CPUTensor::zeros::<half::f16>(shape),
+            (Init::Zeros, DType::I32) => CPUTensor::zeros::<i32>(shape),
+            (Init::Zeros, DType::U32) => CPUTensor::zeros::<u32>(shape),
+            (init, dt) => {
+                anyhow::bail!("{}: cannot initialize {:?} with {:?}", self.name, dt, init)
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(untagged)]
+enum Components {
+    Vector(Vec<Expr>),
+    Scalar(Expr),
+}
+
+/// A member of the metadata uniform, e.g. `{ name = "numel", type = "u32", value = "N" }`.
+/// Vectors take one expression per component.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetadataField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    value: Components,
+}
+
+impl MetadataField {
+    //Scalar type, component count, alignment and size, by the WGSL rules
+    fn layout(&self) -> anyhow::Result<(&str, usize, u64, u64)> {
+        let unsupported = || anyhow::anyhow!("{} has unsupported type {}", self.name, self.ty);
+        let (scalar, n) = match self.ty.strip_prefix("vec") {
+            Some(vector) => {
+                let n = match vector.chars().next().and_then(|c| c.to_digit(10)) {
+                    Some(n @ 2..=4) => n as usize,
+                    _ => anyhow::bail!(
+                        "{} has type {}, but vectors have 2, 3 or 4 components",
+                        self.name,
+                        self.ty
+                    ),
+                };
+                let scalar = vector[1..]
+                    .strip_prefix('<')
+                    .and_then(|s| s.strip_suffix('>'))
+                    .ok_or_else(unsupported)?;
+                (scalar, n)
+            }
+            None => (self.ty.as_str(), 1),
+        };
+        if !["u32", "i32", "f32"].contains(&scalar) {
+            return Err(unsupported());
+        }
+        let align = match n {
+            1 => 4,
+            2 => 8,
+            _ => 16,
+        };
+        Ok((scalar, n, align, 4 * n as u64))
+    }
+
+    fn components(&self) -> Vec<&Expr> {
+        match &self.value {
+            Components::Vector(v) => v.iter().collect(),
+            Components::Scalar(e) => vec![e],
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkloadSpec {
+    pub size: [Expr; 3],
+    pub count: [Expr; 3],
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CostSpec {
+    /// Bytes are those of every tensor, as with [`Cost::from_tensors`].
+    #[serde(default)]
+    pub flops: Option<Expr>,
+}
+
+/// A Python snippet computing every output from the inputs and parameters, which are in
+/// scope by name as numpy arrays and numbers.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReferenceSpec {
+    pub python: String,
+    #[serde(default = "default_tolerance")]
+    pub atol: f32,
+    #[serde(default = "default_tolerance")]
+    pub rtol: f32,
+}
+
+/// # Manifest
+///
+/// A kernel declared in a `.toml` next to its `.wgsl` template, e.g.
+/// `kernels/elementwise/add.toml`, run by [`ManifestKernel`] without any Rust code.
+/// Shapes, workload, template variables, metadata and cost are [`Expr`]s over the parameters.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Defaults to the path of the manifest within `kernels/`, e.g. `elementwise/add`.
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default, rename = "params")]
+    params_table: toml::Table,
+    /// Tera variables, evaluated in order so later ones may use earlier ones.
+    #[serde(default, rename = "template")]
+    template_table: toml::Table,
+    pub inputs: Vec<TensorSpec>,
+    pub outputs: Vec<TensorSpec>,
+    #[serde(default)]
+    pub metadata: Vec<MetadataField>,
+    pub workload: WorkloadSpec,
+    #[serde(default)]
+    pub cost: CostSpec,
+    pub reference: Option<ReferenceSpec>,
+    #[serde(skip)]
+    pub defaults: Params,
+    #[serde(skip)]
+    template: Vec<(String, Expr)>,
+    #[serde(skip)]
+    path: String,
+    #[serde(skip)]
+    text: String,
+}
+
+impl Manifest {
+    /// The largest metadata a manifest may declare.
+    pub const MAX_METADATA_SIZE: u64 = 256;
+
+    /// Loads `<root>/<dir>/<name>.toml` and the `<name>.wgsl` beside it.
+    pub fn load(root: &Path, path: &Path) -> anyhow::Result<Self> {
+        let toml = std::fs::read_to_string(path)?;
+        let mut manifest: Manifest = toml::from_str(&toml)?;
+        let template = path.with_extension("wgsl");
+        manifest.text = std::fs::read_to_string(&template)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", template.display(), e))?;
+        let relative = template.strip_prefix(root.parent().unwrap_or(root))?;
+        manifest.path = relative.to_string_lossy().replace('\\', "/");
+        if manifest.name.is_empty() {
+            let name = path.strip_prefix(root)?.with_extension("");
+            manifest.name = name.to_string_lossy().replace('\\', "/");
+        }
+        manifest.defaults = to_params(&manifest.params_table)?;
+        manifest.template = manifest
+            .template_table
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), Expr::deserialize(value.clone())?)))
+            .collect::<anyhow::Result<_>>()?;
+        manifest.layout()?;
+        Ok(manifest)
+    }
+
+    pub fn template(&'static self) -> Template {
+        Template {
+            path: &self.path,
+            text: &self.text,
+        }
+    }
+
+    /// Values of the template variables at `params`. Shapes, workload, metadata and cost
+    /// may use them as well as the parameters.
+    pub fn template_params(&self, params: &Params) -> anyhow::Result<Params> {
+        let mut values = Params::default();
+        for (key, expr) in &self.template {
+            let value = expr
+                .eval(&params.merge(&values))
+                .map_err(|e| anyhow::anyhow!("Template variable {}: {:#}", key, e))?;
+            values.insert(key, value);
+        }
+        Ok(values)
+    }
+
+    pub fn workload(&self, params: &Params) -> anyhow::Result<Workload> {
+        let eval = |exprs: &[Expr; 3]| -> anyhow::Result<[u32; 3]> {
+            let mut dims = [0; 3];
+            for (dim, expr) in dims.iter_mut().zip(exprs) {
+                *dim = expr.eval_usize(params)? as u32;
+            }
+            Ok(dims)
+        };
+        let [sx, sy, sz] = eval(&self.workload.size)?;
+        let [cx, cy, cz] = eval(&self.workload.count)?;
+        Ok(Workload::new(
+            WorkgroupSize::new(sx, sy, sz),
+            WorkgroupCount::new(cx, cy, cz),
+        ))
+    }
+
+    /// Layout of the metadata uniform, as the shader must declare it.
+    pub fn layout(&self) -> anyhow::Result<StructLayout> {
+        let members = self
+            .metadata
+            .iter()
+            .map(|field| {
+                let (_, _, align, size) = field.layout()?;
+                Ok((field.name.as_str(), field.ty.as_str(), align, size))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let layout = StructLayout::from_members(&format!("{} metadata", self.name), &members);
+        anyhow::ensure!(
+            layout.size <= Self::MAX_METADATA_SIZE,
+            "{} is {} bytes, more than the {} supported",
+            layout.name,
+            layout.size,
+            Self::MAX_METADATA_SIZE
+        );
+        Ok(layout)
+    }
+
+    /// The metadata uniform at `params`, as raw words.
+    pub fn metadata(&self, params: &Params) -> anyhow::Result<ManifestMeta> {
+        let layout = self.layout()?;
+        let mut words = [0u32; Manifest::MAX_METADATA_SIZE as usize / 4];
+        for (field, member) in self.metadata.iter().zip(&layout.members) {
+            let (scalar, n, _, _) = field.layout()?;
+            let components = field.components();
+            anyhow::ensure!(
+                components.len() == n,
+                "{} is a {}, but has {} values",
+                field.name,
+                field.ty,
+                components.len()
+            );
+            for (i, component) in components.into_iter().enumerate() {
+                let value = component
+                    .eval(params)
+                    .and_then(|v| v.as_f64())
+                    .map_err(|e| anyhow::anyhow!("Metadata {}: {:#}", field.name, e))?;
+                words[member.offset as usize / 4 + i] = match scalar {
+                    "u32" => value as u32,
+                    "i32" => value as i32 as u32,
+                    _ => (value as f32).to_bits(),
+                };
+            }
+        }
+        let words = std::array::from_fn(|i| glam::UVec4::from_slice(&words[4 * i..4 * i + 4]));
+        Ok(ManifestMeta { words })
+    }
+}
+
+/// Metadata of a [`ManifestKernel`], laid out by its manifest and written as raw words.
+#[derive(encase::ShaderType, Debug)]
+pub struct ManifestMeta {
+    words: [glam::UVec4; Manifest::MAX_METADATA_SIZE as usize / 16],
+}
+
+//Checked against the shader by `ManifestKernel::new` instead
+impl OpMetadata for ManifestMeta {}
+
+/// # ManifestKernel
+///
+/// A [`Manifest`] at a set of parameters, benchmarked like any other [`KernelBench`].
+#[derive(Debug)]
+pub struct ManifestKernel {
+    manifest: &'static Manifest,
+    params: Params,
+    template_params: Params,
+}
+
+impl ManifestKernel {
+    /// Builds the kernel at the manifest's defaults with `params` replaced, rendering it to
+    /// check its bindings and metadata against the shader.
+    pub fn new(manifest: &'static Manifest, params: &Params) -> anyhow::Result<Self> {
+        let params = manifest.defaults.merge(params);
+        let template_params = manifest.template_params(&params)?;
+        let kernel = Self {
+            manifest,
+            params,
+            template_params,
+        };
+        let source = kernel.source(&manifest.workload(&kernel.scope())?);
+        let (module, info) = source.module()?;
+        manifest
+            .layout()?
+            .compare(&reflect_metadata(&module, &info)?)?;
+        manifest.metadata(&kernel.scope())?;
+        for spec in manifest.inputs.iter().chain(&manifest.outputs) {
+            spec.shape(&kernel.scope())?;
+        }
+        kernel.flops()?;
+        Ok(kernel)
+    }
+
+    fn flops(&self) -> anyhow::Result<usize> {
+        match &self.manifest.cost.flops {
+            Some(flops) => flops
+                .eval_usize(&self.scope())
+                .map_err(|e| anyhow::anyhow!("{} cost.flops: {:#}", self.manifest.name, e)),
+            None => Ok(0),
+        }
+    }
+
+    pub fn manifest(&self) -> &'static Manifest {
+        self.manifest
+    }
+
+    /// The parameters together with the template variables, which every expression but
+    /// those of the template variables themselves is evaluated in.
+    pub fn scope(&self) -> Params {
+        self.params.merge(&self.template_params)
+    }
+
+    fn try_tensors(&self, rng: &mut impl Rng) -> anyhow::Result<Vec<CPUTensor>> {
+        let inputs = self.manifest.inputs.iter().map(|spec| (spec, spec.init));
+        let outputs = self.manifest.outputs.iter().map(|spec| (spec, Init::Zeros));
+        inputs
+            .chain(outputs)
+            .map(|(spec, init)| spec.tensor(spec.shape(&self.scope())?, init, rng))
+            .collect()
+    }
+
+    /// The kernel's tensors, with the inputs drawn from `seed`.
+    pub fn seeded_tensors(&self, seed: u64) -> anyhow::Result<Vec<CPUTensor>> {
+        self.try_tensors(&mut SmallRng::seed_from_u64(seed))
+    }
+
+    pub fn golden(&self, seed: u64) -> Golden {
+        Golden::new(&self.manifest.name, &self.params, seed)
+    }
+
+    //Inputs and expected outputs of the golden for `seed`, which is recorded first if missing
+    fn replay_golden(
+        &self,
+        seed: u64,
+        tensors: &[CPUTensor],
+    ) -> anyhow::Result<(Vec<CPUTensor>, Vec<CPUTensor>)> {
+        let golden = self.golden(seed);
+        if !golden.exists() {
+            KernelBench::record_golden(self, seed)?;
+        }
+        let (inputs, outputs) = golden.load()?;
+        let matches = |stored: &NamedTensors, specs: &[TensorSpec], tensors: &[CPUTensor]| {
+            stored.len() == specs.len()
+                && stored
+                    .iter()
+                    .zip(specs)
+                    .zip(tensors)
+                    .all(|(((name, t), spec), ours)| {
+                        *name == spec.name && t.shape() == ours.shape() && t.dt() == ours.dt()
+                    })
+        };
+        let (ins, outs) = tensors.split_at(self.manifest.inputs.len());
+        if !matches(&inputs, &self.manifest.inputs, ins)
+            || !matches(&outputs, &self.manifest.outputs, outs)
+        {
+            anyhow::bail!(
+                "The golden in {} no longer matches {}, refresh it with `wgpu-bench refresh-goldens {}`",
+                golden.dir.display(),
+                self.manifest.name,
+                self.manifest.name
+            );
+        }
+        let tensors = |stored: NamedTensors| stored.into_iter().map(|(_, t)| t).collect();
+        Ok((tensors(inputs), tensors(outputs)))
+    }
+
+    //Runs the reference with every input and parameter in scope, returning the outputs
+    fn run_python(
+        &self,
+        reference: &ReferenceSpec,
+        tensors: &[CPUTensor],
+    ) -> anyhow::Result<Vec<CPUTensor>> {
+        let python_error = |e: pyo3::PyErr| anyhow::anyhow!("{}: {}", self.manifest.name, e);
+        Python::with_gil(|py| {
+            let locals = PyDict::new(py);
+            for (key, value) in self.scope().iter() {
+                if let Ok(b) = value.parse::<bool>() {
+                    locals.set_item(key, b)
+                } else if let Ok(n) = value.parse::<f64>() {
+                    locals.set_item(key, n)
+                } else {
+                    locals.set_item(key, value)
+                }
+                .map_err(python_error)?;
+            }
+            for (spec, tensor) in self.manifest.inputs.iter().zip(tensors) {
+                anyhow::ensure!(
+                    tensor.dt() == DType::F32,
+                    "References take f32 inputs, {} is {:?}",
+                    spec.name,
+                    tensor.dt()
+                );
+                locals
+                    .set_item(&spec.name, tensor.to_py::<f32>(&py))
+                    .map_err(python_error)?;
+            }
+            py.run(&reference.python, None, Some(locals))
+                .map_err(python_error)?;
+            self.manifest
+                .outputs
+                .iter()
+                .map(|spec| {
+                    let output = locals.get_item(&spec.name).ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "The reference of {} sets no {}",
+                            self.manifest.name,
+                            spec.name
+                        )
+                    })?;
+                    let array: &PyArrayDyn<f32> = output.extract().map_err(python_error)?;
+                    Ok(CPUTensor::from(array))
+                })
+                .collect()
+        })
+    }
+}
+
+impl KernelBench for ManifestKernel {
+    type Metadata = ManifestMeta;
+
+    fn name() -> &'static str {
+        "ManifestKernel"
+    }
+
+    fn label(&self) -> String {
+        self.manifest.name.clone()
+    }
+
+    fn source(&self, workload: &Workload) -> KernelSource {
+        let mut context = self.template_params.to_context();
+        context.insert_workload(workload);
+        KernelSource::render(self.manifest.template(), &context)
+    }
+
+    fn tensors(&self) -> Vec<CPUTensor> {
+        self.try_tensors(&mut SmallRng::from_entropy()).unwrap()
+    }
+
+    fn workload(&self, _: &[CPUTensor]) -> Workload {
+        self.manifest.workload(&self.scope()).unwrap()
+    }
+
+    fn metadata(&self, _: &[CPUTensor]) -> Self::Metadata {
+        self.manifest.metadata(&self.scope()).unwrap()
+    }
+
+    //The inputs are replaced by the golden's, so that its outputs can be compared without Python
+    fn validate(&self, handle: &GPUHandle, tensors: &[CPUTensor]) -> anyhow::Result<()> {
+        let Some(reference) = &self.manifest.reference else {
+            anyhow::bail!("{} declares no reference", self.manifest.name);
+        };
+        let (inputs, expected) = self.replay_golden(Golden::seed()?, tensors)?;
+        let outputs = tensors[inputs.len()..].iter().cloned();
+        let tensors = inputs.into_iter().chain(outputs).collect::<Vec<_>>();
+        let mut gpu_tensors = dispatch_validate(handle, self, &tensors);
+        let outputs = gpu_tensors.split_off(self.manifest.inputs.len());
+        for ((spec, expected), output) in self.manifest.outputs.iter().zip(expected).zip(outputs) {
+            let ours = output.into_cpu(handle)?;
+            expected
+                .all_close(&ours, reference.atol, reference.rtol)
+                .map_err(|e| anyhow::anyhow!("{}: {:#}", spec.name, e))?;
+        }
+        Ok(())
+    }
+
+    fn cost(&self, tensors: &[CPUTensor]) -> Cost {
+        //Evaluated when the kernel was built
+        let flops = self.flops().unwrap();
+        Cost::from_tensors(flops as u64, tensors)
+    }
+
+    fn params(&self) -> Params {
+        self.params.clone()
+    }
+
+    fn record_golden(&self, seed: u64) -> anyhow::Result<Option<Golden>> {
+        let Some(reference) = &self.manifest.reference else {
+            return Ok(None);
+        };
+        let tensors = self.seeded_tensors(seed)?;
+        let expected = self.run_python(reference, &tensors)?;
+        let names = |specs: &'static [TensorSpec]| specs.iter().map(|s| s.name.as_str());
+        let inputs = names(&self.manifest.inputs)
+            .zip(&tensors)
+            .collect::<Vec<_>>();
+        let outputs = names(&self.manifest.outputs)
+            .zip(&expected)
+            .collect::<Vec<_>>();
+        let golden = self.golden(seed);
+        golden.store(&inputs, &outputs)?;
+        Ok(Some(golden))
+    }
+}
+
+/// Every manifest under a directory, with those that failed to load kept as errors.
+#[derive(Debug, Default)]
+pub struct Catalogue {
+    pub manifests: Vec<Manifest>,
+    pub errors: Vec<(PathBuf, anyhow::Error)>,
+}
+
+impl Catalogue {
+    pub fn load(root: &Path) -> Self {
+        let mut catalogue = Self::default();
+        let mut paths = vec![];
+        let mut dirs = vec![root.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(&dir).into_iter().flatten().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|e| e == "toml") {
+                    paths.push(path);
+                }
+            }
+        }
+        paths.sort();
+        for path in paths {
+            match Manifest::load(root, &path) {
+                Ok(manifest) => catalogue.manifests.push(manifest),
+                Err(e) => catalogue.errors.push((path, e)),
+            }
+        }
+        catalogue
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref CATALOGUE: Catalogue =
+        Catalogue::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("kernels"));
+}
+
+/// The manifests in `kernels/`, loaded once.
+pub fn catalogue() -> &'static Catalogue {
+    &CATALOGUE
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn manifest_kernels() {
+        let catalogue = catalogue();
+        assert!(catalogue.errors.is_empty(), "{:?}", catalogue.errors);
+        let axpy = catalogue
+            .manifests
+            .iter()
+            .find(|m| m.name == "elementwise/axpy")
+            .unwrap();
+        assert_eq!(axpy.defaults.to_string(), "N=1048576,alpha=0.5");
+
+        let kernel = ManifestKernel::new(axpy, &params![N = 1022]).unwrap();
+        assert_eq!(kernel.label(), "elementwise/axpy");
+        let tensors = KernelBench::tensors(&kernel);
+        assert_eq!(tensors.len(), 3);
+        assert_eq!(tensors[2].shape().numel(), 1022);
+        assert_eq!(kernel.workload(&tensors).count().as_tuple(), (4, 1, 1));
+        let words = kernel.metadata(&tensors).words[0];
+        assert_eq!((words.x, f32::from_bits(words.y)), (1022, 0.5));
+        assert_eq!(KernelBench::cost(&kernel, &tensors).flops, 2044);
+        assert!(Runnable::bindings(&kernel).is_ok());
+
+        //A multiple of 4 renders the vectorized variant
+        let kernel = ManifestKernel::new(axpy, &params![N = 1024]).unwrap();
+        let source = kernel.source(&kernel.workload(&[]));
+        assert!(source.contains("alias T = vec4<f32>;"));
+        assert_eq!(kernel.metadata(&[]).words[0].x, 256);
+
+        //Goldens are drawn from, and keyed by, a seed
+        let seeded = |seed| {
+            kernel.seeded_tensors(seed).unwrap()[0]
+                .to_vec::<f32>()
+                .unwrap()
+        };
+        assert_eq!(seeded(3), seeded(3));
+        assert_ne!(seeded(3), seeded(4));
+        let golden = kernel.golden(3);
+        assert!(golden
+            .dir
+            .ends_with("elementwise/axpy/N=1024,alpha=0.5/seed=3"));
+
+        //Malformed metadata types are errors rather than panics
+        for ty in [
+            "vec",
+            "vec<f32>",
+            "vecX<f32>",
+            "vec5<f32>",
+            "vec4f32",
+            "vec2<f64>",
+            "f64",
+        ] {
+            let field = format!("name = \"x\"\ntype = \"{}\"\nvalue = \"N\"", ty);
+            let field: MetadataField = toml::from_str(&field).unwrap();
+            assert!(field.layout().is_err(), "{} has a layout", ty);
+        }
+        let field: MetadataField =
+            toml::from_str("name = \"x\"\ntype = \"vec3<u32>\"\nvalue = [\"N\", \"1\", \"2\"]")
+                .unwrap();
+        assert_eq!(field.layout().unwrap(), ("u32", 3, 16, 12));
+
+        //A metadata type that drifted from the shader fails to build
+        let dir = TempDir::new("manifest");
+        let kernels = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("kernels");
+        let toml = std::fs::read_to_string(kernels.join("elementwise/axpy.toml")).unwrap();
+        std::fs::write(dir.join("axpy.toml"), toml.replace("\"f32\"", "\"u32\"")).unwrap();
+        std::fs::copy(kernels.join("elementwise/axpy.wgsl"), dir.join("axpy.wgsl")).unwrap();
+        let drifted = Box::leak(Box::new(
+            Manifest::load(&dir, &dir.join("axpy.toml")).unwrap(),
+        ));
+        let error = ManifestKernel::new(drifted, &params![]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "axpy metadata.alpha is u32, but f32 in the shader's Meta"
+        );
+
+        //So does a cost that isn't a count of FLOPs
+        std::fs::write(dir.join("axpy.toml"), toml.replace("2 * N", "0 - N")).unwrap();
+        let negative = Box::leak(Box::new(
+            Manifest::load(&dir, &dir.join("axpy.toml")).unwrap(),
+        ));
+        let error = ManifestKernel::new(negative, &params![]).unwrap_err();
+        assert!(
+            error.to_string().starts_with("axpy cost.flops: "),
+            "{}",
+            error
+        );
+    }
+}
@@ -7,6 +7,12 @@ pub const STORAGE_BUFFER_ALIGN: usize = 256;
 pub const MIN_STORAGE_BUFFER_SIZE: usize = 16;
 
 pub trait OpMetadata: Sized + ShaderType + WriteInto + std::fmt::Debug {
+    /// Layout of the struct in the uniform buffer, for metadata declared with [`op_metadata!`].
+    /// Hand-written metadata returns `None` and is not checked against the shader.
+    fn layout() -> Option<StructLayout> {
+        None
+    }
+
     fn into_buffer(&self, handle: &GPUHandle) -> GPUBuffer {
         let size: usize = self.size().get() as _;
         let aligned_size = size + (UNIFORM_ALIGN - size % UNIFORM_ALIGN);
@@ -27,3 +33,182 @@ pub trait OpMetadata: Sized + ShaderType + WriteInto + std::fmt::Debug {
         buffer.into()
     }
 }
+
+/// A struct member as laid out in a buffer, e.g. `in_strides: vec3<u32>` at offset 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaMember {
+    pub name: String,
+    pub ty: String,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// # StructLayout
+///
+/// Members and size of a struct, either reflected from the shader or computed for an
+/// [`OpMetadata`] from the WGSL alignment rules and encase's sizes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructLayout {
+    pub name: String,
+    pub members: Vec<MetaMember>,
+    pub size: u64,
+}
+
+impl StructLayout {
+    /// Lays out `(name, WGSL type, alignment, size)` members in order.
+    pub fn from_members(name: &str, members: &[(&str, &str, u64, u64)]) -> Self {
+        let round_up = |n: u64, align: u64| n.div_ceil(align) * align;
+        let mut offset = 0;
+        let mut align = 4;
+        let members = members
+            .iter()
+            .map(|&(name, ty, member_align, size)| {
+                offset = round_up(offset, member_align);
+                align = align.max(member_align);
+                let member = MetaMember {
+                    name: name.to_string(),
+                    ty: ty.to_string(),
+                    offset,
+                    size,
+                };
+                offset += size;
+                member
+            })
+            .collect();
+        Self {
+            name: name.to_string(),
+            members,
+            size: round_up(offset, align),
+        }
+    }
+
+    /// Describes the first difference to `shader`, the layout the shader expects.
+    pub fn compare(&self, shader: &StructLayout) -> anyhow::Result<()> {
+        let describe = |m: &MetaMember| format!("`{}: {}` at offset {}", m.name, m.ty, m.offset);
+        for i in 0..self.members.len().max(shader.members.len()) {
+            match (self.members.get(i), shader.members.get(i)) {
+                (Some(ours), Some(theirs)) if ours.name != theirs.name => anyhow::bail!(
+                    "Member {} of {} is {}, but {} in the shader's {}",
+                    i,
+                    self.name,
+                    describe(ours),
+                    describe(theirs),
+                    shader.name
+                ),
+                (Some(ours), Some(theirs)) if ours.ty != theirs.ty => anyhow::bail!(
+                    "{}.{} is {}, but {} in the shader's {}",
+                    self.name,
+                    ours.name,
+                    ours.ty,
+                    theirs.ty,
+                    shader.name
+                ),
+                (Some(ours), Some(theirs)) if ours.offset != theirs.offset => anyhow::bail!(
+                    "{}.{} is at offset {}, but {} in the shader's {}",
+                    self.name,
+                    ours.name,
+                    ours.offset,
+                    theirs.offset,
+                    shader.name
+                ),
+                (Some(ours), None) => anyhow::bail!(
+                    "{} has {}, which the shader's {} lacks",
+                    self.name,
+                    describe(ours),
+                    shader.name
+                ),
+                (None, Some(theirs)) => anyhow::bail!(
+                    "The shader's {} has {}, which {} lacks",
+                    shader.name,
+                    describe(theirs),
+                    self.name
+                ),
+                _ => {}
+            }
+        }
+        if self.size != shader.size {
+            anyhow::bail!(
+                "{} is {} bytes, but the shader's {} is {} bytes",
+                self.name,
+                self.size,
+                shader.name,
+                shader.size
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Types that can be members of metadata, with their WGSL name and uniform alignment.
+pub trait WgslType: ShaderType {
+    const WGSL: &'static str;
+    const ALIGN: u64;
+}
+
+macro_rules! wgsl_type {
+    ($($t:ty => $wgsl:literal, $align:literal;)*) => {
+        $(impl WgslType for $t {
+            const WGSL: &'static str = $wgsl;
+            const ALIGN: u64 = $align;
+        })*
+    };
+}
+
+wgsl_type! {
+    u32 => "u32", 4;
+    i32 => "i32", 4;
+    f32 => "f32", 4;
+    glam::UVec2 => "vec2<u32>", 8;
+    glam::UVec3 => "vec3<u32>", 16;
+    glam::UVec4 => "vec4<u32>", 16;
+    glam::IVec2 => "vec2<i32>", 8;
+    glam::IVec3 => "vec3<i32>", 16;
+    glam::IVec4 => "vec4<i32>", 16;
+    glam::Vec2 => "vec2<f32>", 8;
+    glam::Vec3 => "vec3<f32>", 16;
+    glam::Vec4 => "vec4<f32>", 16;
+    glam::Mat4 => "mat4x4<f32>", 16;
+}
+
+/// Declares an [`OpMetadata`] struct whose layout is checked against the uniform struct
+/// of the shader before every dispatch.
+///
+/// ```ignore
+/// op_metadata! {
+///     #[derive(derive_new::new)]
+///     pub struct LayerNormMeta {
+///         M: u32,
+///         N: u32,
+///         eps: f32,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! op_metadata {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(encase::ShaderType, Debug)]
+        $vis struct $name {
+            $($field_vis $field: $ty,)*
+        }
+
+        impl $crate::OpMetadata for $name {
+            fn layout() -> Option<$crate::StructLayout> {
+                Some($crate::StructLayout::from_members(
+                    stringify!($name),
+                    &[$((
+                        stringify!($field),
+                        <$ty as $crate::WgslType>::WGSL,
+                        <$ty as $crate::WgslType>::ALIGN,
+                        <$ty as encase::ShaderType>::min_size().get(),
+                    )),*],
+                ))
+            }
+        }
+    };
+}
@@ -0,0 +1,196 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::Path;
+
+use half::{bf16, f16};
+use ndarray::{ArrayD, IxDyn, ShapeBuilder};
+use npyz::{npz, FixedSizeBytes, NpyFile, WriterBuilder};
+
+use crate::{CPUTensor, DType, DataType, NamedTensors};
+
+//C order array from the values of an .npy file in either order
+fn from_values<T: DataType>(
+    values: Vec<T>,
+    shape: &[usize],
+    fortran: bool,
+) -> anyhow::Result<CPUTensor> {
+    let array = match fortran {
+        true => ArrayD::from_shape_vec(IxDyn(shape).f(), values)?,
+        false => ArrayD::from_shape_vec(IxDyn(shape), values)?,
+    };
+    Ok(CPUTensor::from(array.as_standard_layout().into_owned()))
+}
+
+/// # NPY
+///
+/// Tensors of every [`DType`] but the packed [`DType::WQ8`] are read from and written to numpy's
+/// `.npy` and `.npz` formats. Files are written little-endian and read in either byte order.
+impl CPUTensor {
+    pub fn read_npy(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let npy = NpyFile::new(BufReader::new(File::open(path)?))?;
+        Self::from_npy(npy).map_err(|e| anyhow::anyhow!("{}: {:#}", path.display(), e))
+    }
+
+    /// Writes the tensor to a `.npy` file, readable with `numpy.load`.
+    pub fn write_npy(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        std::fs::write(path, self.to_npy()?)?;
+        Ok(())
+    }
+
+    /// Reads every array of an `.npz` archive, as written by `numpy.savez`, in archive order.
+    pub fn read_npz(path: impl AsRef<Path>) -> anyhow::Result<NamedTensors> {
+        let mut archive = npz::NpzArchive::open(&path)?;
+        let names = archive.array_names().map(String::from).collect::<Vec<_>>();
+        names
+            .into_iter()
+            .map(|name| {
+                let npy = archive
+                    .by_name(&name)?
+                    .ok_or_else(|| anyhow::anyhow!("{} is not in the archive", name))?;
+                let tensor =
+                    Self::from_npy(npy).map_err(|e| anyhow::anyhow!("{}: {:#}", name, e))?;
+                Ok((name, tensor))
+            })
+            .collect()
+    }
+
+    /// Writes the tensors to an `.npz` archive, readable with `numpy.load`, replacing any file at `path`.
+    pub fn write_npz(path: impl AsRef<Path>, tensors: &[(&str, &CPUTensor)]) -> anyhow::Result<()> {
+        let mut archive = npz::NpzWriter::create(path)?;
+        for (name, tensor) in tensors {
+            let npy = tensor
+                .to_npy()
+                .map_err(|e| anyhow::anyhow!("{}: {:#}", name, e))?;
+            let zip = archive.zip_writer();
+            zip.start_file(npz::file_name_from_array_name(name), Default::default())?;
+            zip.write_all(&npy)?;
+        }
+        archive.zip_writer().finish()?;
+        Ok(())
+    }
+
+    fn from_npy<R: Read>(npy: NpyFile<R>) -> anyhow::Result<Self> {
+        let shape = npy.shape().iter().map(|&d| d as usize).collect::<Vec<_>>();
+        let fortran = npy.order() == npyz::Order::Fortran;
+        match DType::try_from(npy.dtype())? {
+            DType::Q8 => from_values(npy.into_vec::<i8>()?, &shape, fortran),
+            DType::F16 => from_values(npy.into_vec::<f16>()?, &shape, fortran),
+            DType::BF16 => {
+                let raw = npy.into_vec::<FixedSizeBytes<2>>()?;
+                let values = raw.into_iter().map(|b| bf16::from_le_bytes(b.0)).collect();
+                from_values::<bf16>(values, &shape, fortran)
+            }
+            DType::F32 => from_values(npy.into_vec::<f32>()?, &shape, fortran),
+            DType::I32 => from_values(npy.into_vec::<i32>()?, &shape, fortran),
+            DType::U32 => from_values(npy.into_vec::<u32>()?, &shape, fortran),
+            DType::WQ8 => unreachable!("WQ8 has no .npy type"),
+        }
+    }
+
+    //The complete .npy file, so that it can be written to disk or to an archive alike
+    fn to_npy(&self) -> anyhow::Result<Vec<u8>> {
+        match self.dt() {
+            DType::Q8 => self.npy_from_values(self.to_vec::<i8>()?),
+            DType::F16 => self.npy_from_values(self.to_vec::<f16>()?),
+            DType::BF16 => {
+                let values = self.to_vec::<bf16>()?.into_iter();
+                self.npy_from_values(values.map(|x| FixedSizeBytes(x.to_le_bytes())))
+            }
+            DType::F32 => self.npy_from_values(self.to_vec::<f32>()?),
+            DType::I32 => self.npy_from_values(self.to_vec::<i32>()?),
+            DType::U32 => self.npy_from_values(self.to_vec::<u32>()?),
+            DType::WQ8 => Err(self.dt().npy_type().unwrap_err()),
+        }
+    }
+
+    fn npy_from_values<T: npyz::Serialize>(
+        &self,
+        values: impl IntoIterator<Item = T>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let shape = self.shape().to_vec().into_iter().map(|d| d as u64);
+        let mut npy = vec![];
+        let mut writer = npyz::WriteOptions::<T>::new()
+            .dtype(self.dt().npy_type()?)
+            .shape(&shape.collect::<Vec<_>>())
+            .writer(&mut npy)
+            .begin_nd()?;
+        writer.extend(values)?;
+        writer.finish()?;
+        Ok(npy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn npy_roundtrip() {
+        let dir = TempDir::new("npy");
+
+        let values = [0.5f32, -1.25, 3.0, 1e-3, -7.5, 42.0];
+        let f32s = CPUTensor::from_slice(&values, shape![2, 3]);
+        let f16s = CPUTensor::from_slice(&values.map(half::f16::from_f32), shape![3, 2]);
+        let bf16s = CPUTensor::from_slice(&values.map(half::bf16::from_f32), shape![6]);
+        let i32s = CPUTensor::from_slice(&[-3i32, 0, 7], shape![3]);
+        let u32s = CPUTensor::from_slice(&[1u32, u32::MAX], shape![1, 2]);
+        let q8s = CPUTensor::from_slice(&[-128i8, 0, 127, 5], shape![2, 2]);
+        let tensors = [f32s, f16s, bf16s, i32s, u32s, q8s];
+
+        for (i, tensor) in tensors.iter().enumerate() {
+            let path = dir.join(format!("{}.npy", i));
+            tensor.write_npy(&path).unwrap();
+            let read = CPUTensor::read_npy(&path).unwrap();
+            assert_eq!(read.dt(), tensor.dt());
+            assert_eq!(read.shape(), tensor.shape());
+            assert_eq!(read.storage().as_bytes(), tensor.storage().as_bytes());
+        }
+
+        let names = ["f32", "f16", "bf16", "i32", "u32", "q8"];
+        let named = names.iter().copied().zip(&tensors).collect::<Vec<_>>();
+        CPUTensor::write_npz(dir.join("all.npz"), &named).unwrap();
+        let read = CPUTensor::read_npz(dir.join("all.npz")).unwrap();
+        assert_eq!(read.len(), tensors.len());
+        for (name, tensor) in named {
+            let (_, ours) = read.iter().find(|(n, _)| n == name).unwrap();
+            assert_eq!(ours.dt(), tensor.dt());
+            assert_eq!(ours.storage().as_bytes(), tensor.storage().as_bytes());
+        }
+
+        //Big-endian and Fortran order files, as numpy writes them for `a.astype('>f4')` and `np.asfortranarray(a)`
+        let header = |descr: &str, fortran: bool, shape: &str| {
+            let dict = format!(
+                "{{'descr': '{}', 'fortran_order': {}, 'shape': ({}), }}",
+                descr,
+                if fortran { "True" } else { "False" },
+                shape
+            );
+            let padding = 64 - (10 + dict.len() + 1) % 64;
+            let dict = format!("{}{}\n", dict, " ".repeat(padding));
+            let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+            bytes.extend((dict.len() as u16).to_le_bytes());
+            bytes.extend(dict.as_bytes());
+            bytes
+        };
+        let mut big = header(">f4", false, "2,");
+        big.extend(1.5f32.to_be_bytes());
+        big.extend((-2f32).to_be_bytes());
+        std::fs::write(dir.join("big.npy"), big).unwrap();
+        let read = CPUTensor::read_npy(dir.join("big.npy")).unwrap();
+        assert_eq!(read.to_vec::<f32>().unwrap(), [1.5, -2.0]);
+
+        //[[1, 2, 3], [4, 5, 6]] stored column by column
+        let mut fortran = header("<i4", true, "2, 3");
+        for x in [1i32, 4, 2, 5, 3, 6] {
+            fortran.extend(x.to_le_bytes());
+        }
+        std::fs::write(dir.join("fortran.npy"), fortran).unwrap();
+        let read = CPUTensor::read_npy(dir.join("fortran.npy")).unwrap();
+        assert_eq!(read.to_vec::<i32>().unwrap(), [1, 2, 3, 4, 5, 6]);
+
+        let quantized =
+            Quantizer::new(Quantization::SInt8).quantize(CPUTensor::randn::<f32>(shape![4, 16]));
+        assert!(quantized.write_npy(dir.join("wq8.npy")).is_err());
+    }
+}
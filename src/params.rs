@@ -0,0 +1,149 @@
+use std::{fmt::Display, str::FromStr};
+
+/// # Params
+///
+/// An ordered set of named parameters identifying a benchmark point, e.g. `M=2048,N=512`.
+/// Values are stored as their `Display` representation and parsed back on access.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Params(Vec<(String, String)>);
+
+impl Params {
+    /// Sets `key`, replacing its value if already present so the original order is kept.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Display) {
+        let (key, value) = (key.into(), value.to_string());
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, existing)) => *existing = value,
+            None => self.0.push((key, value)),
+        }
+    }
+
+    pub fn with(mut self, key: impl Into<String>, value: impl Display) -> Self {
+        self.insert(key, value);
+        self
+    }
+
+    pub fn get<T: FromStr>(&self, key: &str) -> anyhow::Result<T>
+    where
+        T::Err: Display,
+    {
+        let (_, value) = self
+            .0
+            .iter()
+            .find(|(k, _)| k == key)
+            .ok_or_else(|| anyhow::anyhow!("Missing parameter {} in {}", key, self))?;
+        value
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid parameter {}={}: {}", key, value, e))
+    }
+
+    /// Returns these parameters with every parameter of `other` inserted.
+    pub fn merge(&self, other: &Params) -> Params {
+        let mut merged = self.clone();
+        for (key, value) in other.iter() {
+            merged.insert(key, value);
+        }
+        merged
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.0.iter().any(|(k, _)| k == key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// A Tera context holding every parameter, as a bool or number where it parses as one.
+    pub fn to_context(&self) -> tera::Context {
+        let mut context = tera::Context::new();
+        for (key, value) in self.iter() {
+            if let Ok(b) = value.parse::<bool>() {
+                context.insert(key, &b);
+            } else if let Ok(i) = value.parse::<i64>() {
+                context.insert(key, &i);
+            } else if let Ok(f) = value.parse::<f64>() {
+                context.insert(key, &f);
+            } else {
+                context.insert(key, value);
+            }
+        }
+        context
+    }
+}
+
+impl Display for Params {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "default");
+        }
+        let pairs = self
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>();
+        write!(f, "{}", pairs.join(","))
+    }
+}
+
+impl FromStr for Params {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut params = Params::default();
+        if s.is_empty() || s == "default" {
+            return Ok(params);
+        }
+        for pair in s.split(',') {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Expected key=value, got {:?}", pair))?;
+            params.insert(key.trim(), value.trim());
+        }
+        Ok(params)
+    }
+}
+
+//Serialized in its display form, e.g. "M=2048,N=512"
+impl serde::Serialize for Params {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Params {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[macro_export]
+macro_rules! params {
+    ($($key:ident = $value:expr),*$(,)*) => ({
+        #[allow(unused_mut)]
+        let mut params = $crate::Params::default();
+        $(params.insert(stringify!($key), $value);)*
+        params
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn params_roundtrip() {
+        let mut params = params![M = 2048, N = 512, eps = 1e-5];
+        assert_eq!(params.to_string(), "M=2048,N=512,eps=0.00001");
+        params.insert("M", 1024);
+        assert_eq!(params.get::<usize>("M").unwrap(), 1024);
+        assert!(params.get::<usize>("K").is_err());
+
+        let parsed = "M=1024,N=512,eps=0.00001".parse::<Params>().unwrap();
+        assert_eq!(parsed, params);
+        assert_eq!(params![].to_string(), "default");
+    }
+}
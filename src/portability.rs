@@ -0,0 +1,208 @@
+use std::{fmt::Display, path::Path};
+
+use naga::{
+    back::{glsl, hlsl, msl, spv},
+    proc::BoundsCheckPolicies,
+    valid::ModuleInfo,
+    Module, ShaderStage,
+};
+use tabled::{builder::Builder, settings::Style};
+
+use crate::KernelSource;
+
+/// A naga back-end, standing in for the platform that consumes its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Vulkan
+    SpirV,
+    /// Metal
+    Msl,
+    /// D3D12
+    Hlsl,
+    /// OpenGL ES 3.1, as used by wgpu's GL backend
+    Glsl,
+}
+
+impl Backend {
+    pub const ALL: [Backend; 4] = [Backend::SpirV, Backend::Msl, Backend::Hlsl, Backend::Glsl];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Backend::SpirV => "spv",
+            Backend::Msl => "metal",
+            Backend::Hlsl => "hlsl",
+            Backend::Glsl => "comp",
+        }
+    }
+
+    /// Translates a validated module with the `main` entry point. SPIR-V is returned as its
+    /// binary, every other language as text.
+    pub fn translate(&self, module: &Module, info: &ModuleInfo) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Backend::SpirV => {
+                let pipeline = spv::PipelineOptions {
+                    shader_stage: ShaderStage::Compute,
+                    entry_point: "main".to_string(),
+                };
+                let words =
+                    spv::write_vec(module, info, &spv::Options::default(), Some(&pipeline))?;
+                Ok(bytemuck::cast_slice(&words).to_vec())
+            }
+            Backend::Msl => {
+                let (source, translation) = msl::write_string(
+                    module,
+                    info,
+                    &msl::Options::default(),
+                    &msl::PipelineOptions::default(),
+                )?;
+                //Entry points that fail are reported here rather than as an error
+                for entry_point in translation.entry_point_names {
+                    entry_point?;
+                }
+                Ok(source.into_bytes())
+            }
+            Backend::Hlsl => {
+                let mut source = String::new();
+                hlsl::Writer::new(&mut source, &hlsl::Options::default()).write(module, info)?;
+                Ok(source.into_bytes())
+            }
+            Backend::Glsl => {
+                let mut source = String::new();
+                let pipeline = glsl::PipelineOptions {
+                    shader_stage: ShaderStage::Compute,
+                    entry_point: "main".to_string(),
+                    multiview: None,
+                };
+                glsl::Writer::new(
+                    &mut source,
+                    module,
+                    info,
+                    &glsl::Options::default(),
+                    &pipeline,
+                    BoundsCheckPolicies::default(),
+                )?
+                .write()?;
+                Ok(source.into_bytes())
+            }
+        }
+    }
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Backend::SpirV => "SPIR-V",
+            Backend::Msl => "MSL",
+            Backend::Hlsl => "HLSL",
+            Backend::Glsl => "GLSL",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// # Portability
+///
+/// The outcome of translating a kernel's WGSL through every naga back-end, so that a shader
+/// that only compiles on the benchmarking machine is caught without the other hardware.
+#[derive(Debug)]
+pub struct Portability {
+    pub kernel: String,
+    pub translations: Vec<(Backend, anyhow::Result<Vec<u8>>)>,
+}
+
+impl Portability {
+    /// WGSL that naga rejects fails on every back-end with the validation error.
+    pub fn check(kernel: impl Into<String>, source: &KernelSource) -> Self {
+        let module = source.module();
+        let translations = Backend::ALL
+            .iter()
+            .map(|backend| {
+                let translated = match &module {
+                    Ok((module, info)) => backend.translate(module, info),
+                    Err(e) => Err(anyhow::anyhow!("{:#}", e)),
+                };
+                (*backend, translated)
+            })
+            .collect();
+        Self {
+            kernel: kernel.into(),
+            translations,
+        }
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = (Backend, &anyhow::Error)> {
+        self.translations
+            .iter()
+            .filter_map(|(backend, result)| Some((*backend, result.as_ref().err()?)))
+    }
+
+    pub fn portable(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// Writes each successful translation to `dir/<kernel>.<extension>`.
+    pub fn dump(&self, dir: &Path) -> anyhow::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        let stem = self.kernel.replace(['/', '\\', ' '], "_");
+        for (backend, result) in &self.translations {
+            if let Ok(translated) = result {
+                let path = dir.join(format!("{}.{}", stem, backend.extension()));
+                std::fs::write(path, translated)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A Markdown table of which back-ends each kernel translates to, followed by the reason
+/// for every failure.
+pub fn portability_table(reports: &[Portability]) -> String {
+    let mut builder = Builder::default();
+    builder.push_record(
+        std::iter::once("Kernel".to_string()).chain(Backend::ALL.iter().map(|b| b.to_string())),
+    );
+    for report in reports {
+        let cells = report.translations.iter().map(|(_, result)| match result {
+            Ok(_) => "ok".to_string(),
+            Err(_) => "FAILED".to_string(),
+        });
+        builder.push_record(std::iter::once(report.kernel.clone()).chain(cells));
+    }
+    let mut table = builder.build().with(Style::markdown()).to_string();
+    for report in reports {
+        for (backend, e) in report.failures() {
+            table += &format!("\n{} on {}: {:#}", report.kernel, backend, e);
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn portability_report() {
+        let source = KernelSource::from(
+            "@group(0) @binding(0) var<storage, read_write> X: array<f32>;
+            @compute @workgroup_size(64) fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+                X[id.x] = 2.0 * X[id.x];
+            }"
+            .to_string(),
+        );
+        let report = Portability::check("scale", &source);
+        assert!(report.portable(), "{}", portability_table(&[report]));
+
+        let dir = TempDir::new("portability");
+        report.dump(&dir).unwrap();
+        for backend in Backend::ALL {
+            assert!(dir.join(format!("scale.{}", backend.extension())).exists());
+        }
+
+        let table = portability_table(&[report]);
+        assert!(table.contains("| scale  | ok     | ok  | ok   | ok   |"));
+
+        let broken = Portability::check("broken", &KernelSource::from("fn".to_string()));
+        assert_eq!(broken.failures().count(), Backend::ALL.len());
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    cell::{Cell, RefCell},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use wgpu::QuerySet;
+
+use crate::{GPUHandle, QueryPair, MAX_QUERIES};
+
+/// Number of query sets in the ring.
+pub const QUERY_SETS: usize = 4;
+
+/// Durations of individually timed dispatches, in raw GPU ticks.
+#[derive(Debug, Clone, Default)]
+pub struct DispatchSamples {
+    pub durations: Vec<u64>,
+    pub first: Vec<u64>, //Duration of the first dispatch of every sample
+}
+
+/// A query set together with the buffers used to read it back.
+struct QuerySlot {
+    query_set: QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    destination_buffer: wgpu::Buffer,
+    groups: RefCell<Vec<u32>>, //Number of query pairs written by each reservation
+    pending: Cell<bool>,       //Resolved and awaiting readback
+    mapped: Arc<AtomicBool>,
+}
+
+impl QuerySlot {
+    fn new(handle: &GPUHandle) -> Self {
+        let query_set = handle.device().create_query_set(&wgpu::QuerySetDescriptor {
+            count: MAX_QUERIES,
+            ty: wgpu::QueryType::Timestamp,
+            label: None,
+        });
+
+        let size = MAX_QUERIES as u64 * std::mem::size_of::<u64>() as u64;
+
+        let resolve_buffer = handle.device().create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::QUERY_RESOLVE,
+            mapped_at_creation: false,
+        });
+
+        let destination_buffer = handle.device().create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            query_set,
+            resolve_buffer,
+            destination_buffer,
+            groups: RefCell::new(vec![]),
+            pending: Cell::new(false),
+            mapped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+/// # QueryPool
+///
+/// A ring of timestamp query sets.
+/// When the current set is full it is resolved and mapped for readback, and writing
+/// continues in the next set. A set is only reused once its timestamps have been drained,
+/// so benchmark duration is not bounded by `MAX_QUERIES`.
+pub struct QueryPool {
+    handle: GPUHandle,
+    slots: Vec<QuerySlot>,
+    current_slot: Cell<usize>,
+    current_query: Cell<QueryPair>,
+    elapsed: Cell<u64>, //Ticks drained since the last call to `drain`
+    samples: RefCell<Option<DispatchSamples>>,
+}
+
+impl QueryPool {
+    pub fn new(handle: GPUHandle) -> Self {
+        let slots = (0..QUERY_SETS).map(|_| QuerySlot::new(&handle)).collect();
+        Self {
+            handle,
+            slots,
+            current_slot: Cell::new(0),
+            current_query: QueryPair::first().into(),
+            elapsed: Cell::new(0),
+            samples: RefCell::new(None),
+        }
+    }
+
+    /// Starts keeping the duration of every query pair, replacing previous samples.
+    pub fn record_samples(&self) {
+        self.samples.replace(Some(DispatchSamples::default()));
+    }
+
+    /// Returns the durations recorded since `record_samples`.
+    pub fn samples(&self) -> Option<DispatchSamples> {
+        self.samples.borrow().clone()
+    }
+
+    fn slot(&self) -> &QuerySlot {
+        &self.slots[self.current_slot.get()]
+    }
+
+    pub fn query_set(&self) -> &QuerySet {
+        &self.slot().query_set
+    }
+
+    pub fn current_query(&self) -> QueryPair {
+        self.current_query.get()
+    }
+
+    //Fetches the current query as ComputePassTimestampWrites
+    pub fn timestamp_writes(&self) -> wgpu::ComputePassTimestampWrites {
+        wgpu::ComputePassTimestampWrites {
+            query_set: self.query_set(),
+            beginning_of_pass_write_index: Some(self.current_query().start),
+            end_of_pass_write_index: Some(self.current_query().end),
+        }
+    }
+
+    pub fn increment_query(&self) {
+        self.advance(1);
+    }
+
+    /// Ensures `pairs` consecutive query pairs fit in the current set,
+    /// returning the index of the first query.
+    /// Must be followed by `advance(pairs)` once the queries are encoded.
+    pub fn reserve(&self, pairs: u32) -> u32 {
+        assert!(
+            pairs * 2 <= MAX_QUERIES,
+            "Cannot time {} dispatches individually, at most {} fit in a query set",
+            pairs,
+            MAX_QUERIES / 2
+        );
+        if self.current_query().start + pairs * 2 > MAX_QUERIES {
+            self.flush();
+        }
+        self.current_query().start
+    }
+
+    /// Moves past `pairs` written query pairs, flushing the set once it is full.
+    pub fn advance(&self, pairs: u32) {
+        self.slot().groups.borrow_mut().push(pairs);
+        let pair = self.current_query.get();
+        self.current_query.set(QueryPair {
+            start: pair.start + pairs * 2,
+            end: pair.end + pairs * 2,
+        });
+        if self.current_query().end >= MAX_QUERIES {
+            self.flush();
+        }
+    }
+
+    /// Resolves the queries written to the current set, starts reading them back,
+    /// and moves on to the next set in the ring.
+    fn flush(&self) {
+        let written = self.current_query().start;
+        if written == 0 {
+            return;
+        }
+        let slot = self.slot();
+        let pass_query = QueryPair {
+            start: 0,
+            end: written - 1,
+        };
+        log::trace!(
+            "Flushing query set {}: {:?}",
+            self.current_slot.get(),
+            pass_query
+        );
+
+        let mut encoder = self
+            .handle
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.resolve_query_set(&slot.query_set, pass_query.into(), &slot.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &slot.resolve_buffer,
+            0,
+            &slot.destination_buffer,
+            0,
+            pass_query.size(),
+        );
+        self.handle.queue().submit(Some(encoder.finish()));
+
+        let mapped = slot.mapped.clone();
+        mapped.store(false, Ordering::Release);
+        slot.destination_buffer
+            .slice(..pass_query.end_address())
+            .map_async(wgpu::MapMode::Read, move |result| {
+                result.expect("Failed to map timestamp buffer");
+                mapped.store(true, Ordering::Release);
+            });
+        slot.pending.set(true);
+
+        let next = (self.current_slot.get() + 1) % self.slots.len();
+        self.drain_slot(next);
+        self.current_slot.set(next);
+        self.current_query.set(QueryPair::first());
+    }
+
+    /// Blocks until the readback of a set has completed and accumulates its timestamps.
+    fn drain_slot(&self, index: usize) {
+        let slot = &self.slots[index];
+        if !slot.pending.replace(false) {
+            return;
+        }
+        let groups = slot.groups.take();
+        let written = groups.iter().sum::<u32>() * 2;
+        while !slot.mapped.load(Ordering::Acquire) {
+            self.handle.device().poll(wgpu::Maintain::Wait);
+        }
+        let byte_range = 0..(written as usize * std::mem::size_of::<u64>()) as u64;
+        let timestamps: Vec<u64> = {
+            let timestamp_view = slot.destination_buffer.slice(byte_range).get_mapped_range();
+            (*bytemuck::cast_slice(&timestamp_view)).to_vec()
+        };
+        log::trace!("Timestamps: {:?}", timestamps);
+        slot.destination_buffer.unmap();
+        self.elapsed
+            .set(self.elapsed.get() + Self::hardware_elapsed(&timestamps));
+
+        if let Some(samples) = self.samples.borrow_mut().as_mut() {
+            let mut durations = timestamps.chunks_exact(2).map(|pair| pair[1] - pair[0]);
+            for pairs in groups {
+                let group = durations.by_ref().take(pairs as usize).collect::<Vec<_>>();
+                samples.first.push(group[0]);
+                samples.durations.extend(group);
+            }
+        }
+    }
+
+    /// Resolves every outstanding query and returns the ticks elapsed since the last drain.
+    pub fn drain(&self) -> u64 {
+        self.flush();
+        let current = self.current_slot.get();
+        for offset in 1..=self.slots.len() {
+            self.drain_slot((current + offset) % self.slots.len());
+        }
+        self.elapsed.replace(0)
+    }
+
+    pub fn hardware_elapsed(timestamps: &[u64]) -> u64 {
+        assert!(timestamps.len() % 2 == 0);
+        let mut elapsed = 0;
+        for i in (0..timestamps.len()).step_by(2) {
+            elapsed += timestamps[i + 1] - timestamps[i];
+        }
+        elapsed
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{percentile, Cost, GPUHandle, Params};
+
+/// The adapter a record was measured on.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AdapterRecord {
+    pub name: String,
+    pub backend: String,
+    pub driver: String,
+    pub fingerprint: String,
+}
+
+impl From<&GPUHandle> for AdapterRecord {
+    fn from(handle: &GPUHandle) -> Self {
+        let info = handle.adapter_info();
+        Self {
+            name: info.name.clone(),
+            backend: format!("{:?}", info.backend),
+            driver: info.driver.clone(),
+            fingerprint: handle.fingerprint(),
+        }
+    }
+}
+
+/// Timing of a kernel at one parameter point, in nanoseconds per dispatch.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct BenchRecord {
+    pub kernel: String,
+    pub params: Params,
+    pub adapter: AdapterRecord,
+    pub samples: usize,
+    pub mean_ns: f64,
+    pub median_ns: f64,
+    pub min_ns: f64,
+    pub max_ns: f64,
+    pub stddev_ns: f64, //Sample standard deviation
+    pub overhead_subtracted: bool,
+    pub flops: u64,
+    pub bytes: u64,
+    pub flops_per_s: f64, //At the mean
+    pub bytes_per_s: f64,
+}
+
+impl BenchRecord {
+    pub fn new(
+        kernel: impl Into<String>,
+        params: Params,
+        adapter: AdapterRecord,
+        samples: &[f64],
+        cost: Cost,
+        overhead_subtracted: bool,
+    ) -> Self {
+        assert!(!samples.is_empty());
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let variance = match n {
+            1 => 0.0,
+            _ => sorted.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1) as f64,
+        };
+        Self {
+            kernel: kernel.into(),
+            params,
+            adapter,
+            samples: n,
+            mean_ns: mean,
+            median_ns: percentile(&sorted, 50.0),
+            min_ns: sorted[0],
+            max_ns: sorted[n - 1],
+            stddev_ns: variance.sqrt(),
+            overhead_subtracted,
+            flops: cost.flops,
+            bytes: cost.bytes,
+            flops_per_s: cost.flops as f64 * 1e9 / mean,
+            bytes_per_s: cost.bytes as f64 * 1e9 / mean,
+        }
+    }
+
+    pub fn cost(&self) -> Cost {
+        Cost::new(self.flops, self.bytes)
+    }
+}
+
+/// Outcome of checking a kernel against its reference implementation.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ValidationRecord {
+    pub kernel: String,
+    pub params: Params,
+    pub adapter: AdapterRecord,
+    pub valid: bool,
+    pub error: Option<String>,
+}
+
+impl ValidationRecord {
+    pub fn new(
+        kernel: impl Into<String>,
+        params: Params,
+        adapter: AdapterRecord,
+        result: &anyhow::Result<()>,
+    ) -> Self {
+        Self {
+            kernel: kernel.into(),
+            params,
+            adapter,
+            valid: result.is_ok(),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! CPU implementations of the benchmarked ops, so kernels validate without Python.
+//! Every op computes in f64 and rounds to f32 once, at the end.
+#![allow(non_snake_case)]
+use anyhow::{anyhow, bail};
+use ndarray::{Array1, Array3, ArrayD, Axis, IxDyn};
+
+use crate::{CPUTensor, DType, Quantization, Quantizer};
+
+fn to_f64(tensor: &CPUTensor) -> anyhow::Result<ArrayD<f64>> {
+    if tensor.dt() != DType::F32 {
+        bail!("Reference ops take f32 tensors, got {:?}", tensor.dt());
+    }
+    Ok(unsafe { tensor.to_array_view_unchecked::<f32>() }.mapv(f64::from))
+}
+
+fn to_f32(array: ArrayD<f64>) -> CPUTensor {
+    CPUTensor::from(array.as_standard_layout().mapv(|x| x as f32))
+}
+
+//Loads a per-feature parameter, which must match the last dimension of the input
+fn feature_param(param: &CPUTensor, name: &str, features: usize) -> anyhow::Result<Array1<f64>> {
+    to_f64(param)?.into_shape(features).map_err(|_| {
+        anyhow!(
+            "{} must have shape [{}], got {:?}",
+            name,
+            features,
+            param.shape()
+        )
+    })
+}
+
+//Normalizes each row over the last dimension, centring it first for LayerNorm
+fn normalize(
+    input: &CPUTensor,
+    scale: &CPUTensor,
+    bias: Option<&CPUTensor>,
+    eps: f32,
+    centre: bool,
+) -> anyhow::Result<CPUTensor> {
+    let mut x = to_f64(input)?;
+    let last = Axis(
+        x.ndim()
+            .checked_sub(1)
+            .ok_or_else(|| anyhow!("Cannot normalize a scalar"))?,
+    );
+    let N = x.len_of(last);
+    let scale = feature_param(scale, "scale", N)?;
+    let bias = match bias {
+        Some(bias) => feature_param(bias, "bias", N)?,
+        None => Array1::zeros(N),
+    };
+    for mut row in x.lanes_mut(last) {
+        let mean = if centre { row.sum() / N as f64 } else { 0.0 };
+        let var = row.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / N as f64;
+        let rstd = 1.0 / (var + eps as f64).sqrt();
+        for (i, v) in row.iter_mut().enumerate() {
+            *v = (*v - mean) * rstd * scale[i] + bias[i];
+        }
+    }
+    Ok(to_f32(x))
+}
+
+/// LayerNorm over the last dimension of `input`, as `torch.nn.functional.layer_norm`.
+pub fn layer_norm(
+    input: &CPUTensor,
+    scale: &CPUTensor,
+    bias: &CPUTensor,
+    eps: f32,
+) -> anyhow::Result<CPUTensor> {
+    normalize(input, scale, Some(bias), eps, true)
+}
+
+/// RMSNorm over the last dimension of `input`: `x / sqrt(mean(x²) + eps) * scale`.
+pub fn rms_norm(input: &CPUTensor, scale: &CPUTensor, eps: f32) -> anyhow::Result<CPUTensor> {
+    normalize(input, scale, None, eps, false)
+}
+
+//Views a tensor of rank >= 2 as a stack of matrices, transposing each if requested
+fn matrices(tensor: &CPUTensor, transpose: bool) -> anyhow::Result<(Vec<usize>, Array3<f64>)> {
+    let shape = tensor.shape().to_vec();
+    if shape.len() < 2 {
+        bail!("Matmul operands need rank >= 2, got {:?}", tensor.shape());
+    }
+    let (batch, matrix) = shape.split_at(shape.len() - 2);
+    let mut stack = to_f64(tensor)?.into_shape((batch.iter().product(), matrix[0], matrix[1]))?;
+    if transpose {
+        stack.swap_axes(1, 2);
+    }
+    Ok((batch.to_vec(), stack))
+}
+
+/// Batched matmul over the last two dimensions, `a @ b` with either operand transposed first
+/// (as `torch.permute(x, (0, 2, 1))`). Leading dimensions must match.
+pub fn matmul(
+    a: &CPUTensor,
+    b: &CPUTensor,
+    trans_a: bool,
+    trans_b: bool,
+) -> anyhow::Result<CPUTensor> {
+    let (a_batch, a) = matrices(a, trans_a)?;
+    let (b_batch, b) = matrices(b, trans_b)?;
+    if a_batch != b_batch {
+        bail!("Batch dimensions differ: {:?} != {:?}", a_batch, b_batch);
+    }
+    let (M, K, N) = (a.len_of(Axis(1)), a.len_of(Axis(2)), b.len_of(Axis(2)));
+    if K != b.len_of(Axis(1)) {
+        bail!("Inner dimensions differ: {} != {}", K, b.len_of(Axis(1)));
+    }
+    let mut out = Array3::zeros((a.len_of(Axis(0)), M, N));
+    for ((a, b), mut out) in a.outer_iter().zip(b.outer_iter()).zip(out.outer_iter_mut()) {
+        ndarray::linalg::general_mat_mul(1.0, &a, &b, 0.0, &mut out);
+    }
+    let shape = [a_batch, vec![M, N]].concat();
+    Ok(to_f32(out.into_shape(IxDyn(&shape))?))
+}
+
+/// `a @ b` for `b` packed with [`Quantization::SInt8`], dequantized as the shader unpacks it.
+pub fn qmatmul(a: &CPUTensor, b: &CPUTensor) -> anyhow::Result<CPUTensor> {
+    if b.dt() != DType::WQ8 {
+        bail!("Expected a {:?} weight, got {:?}", DType::WQ8, b.dt());
+    }
+    let b = Quantizer::new(Quantization::SInt8).dequantize(b.clone());
+    matmul(a, &b, false, false)
+}
+
+/// How RoPE pairs up the components of a head to rotate them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RopeStyle {
+    /// `x[i]` rotates with `x[i + dim / 2]`, as in GPT-NeoX and mlx's `nn.RoPE`.
+    HalfSplit,
+    /// `x[2i]` rotates with `x[2i + 1]`, as in GPT-J and `rotary_embedding_torch`.
+    Interleaved,
+}
+
+/// Rotary embedding of the first `dim` components of every head in `input`, laid out as
+/// `[..., seq_len, head_dim]`. At position `p` (counted from `offset`) pair `i` is rotated by
+/// `p * base^(-2i / dim)`; components past `dim` are copied unchanged.
+pub fn rope(
+    input: &CPUTensor,
+    style: RopeStyle,
+    dim: usize,
+    base: f64,
+    offset: usize,
+) -> anyhow::Result<CPUTensor> {
+    let shape = input.shape().to_vec();
+    if shape.len() < 2 {
+        bail!("RoPE input needs rank >= 2, got {:?}", input.shape());
+    }
+    let (SL, HD) = (shape[shape.len() - 2], shape[shape.len() - 1]);
+    if dim % 2 != 0 || dim > HD {
+        bail!(
+            "RoPE dim {} must be even and at most the head dim {}",
+            dim,
+            HD
+        );
+    }
+    let mut x = to_f64(input)?.into_shape((input.shape().numel() / (SL * HD), SL, HD))?;
+    let half = dim / 2;
+    for mut head in x.outer_iter_mut() {
+        for (p, mut row) in head.outer_iter_mut().enumerate() {
+            let position = (p + offset) as f64;
+            for i in 0..half {
+                let theta = position * base.powf(-2.0 * i as f64 / dim as f64);
+                let (sin, cos) = theta.sin_cos();
+                let (j, k) = match style {
+                    RopeStyle::HalfSplit => (i, i + half),
+                    RopeStyle::Interleaved => (2 * i, 2 * i + 1),
+                };
+                let (x1, x2) = (row[j], row[k]);
+                row[j] = x1 * cos - x2 * sin;
+                row[k] = x1 * sin + x2 * cos;
+            }
+        }
+    }
+    Ok(to_f32(x.into_shape(IxDyn(&shape))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn reference_ops() {
+        let x = CPUTensor::from_slice(&[1f32, 2., 3., 4.], shape![1, 4]);
+        let ones = CPUTensor::from_slice(&[1f32; 4], shape![4]);
+        let zeros = CPUTensor::zeros::<f32>(shape![4]);
+
+        //mean 2.5, variance 1.25
+        let std = 1.25f32.sqrt();
+        let expected = [-1.5 / std, -0.5 / std, 0.5 / std, 1.5 / std];
+        let layer_normed = layer_norm(&x, &ones, &zeros, 0.0).unwrap();
+        layer_normed
+            .all_close(&CPUTensor::from_slice(&expected, shape![1, 4]), 1e-6, 1e-6)
+            .unwrap();
+
+        //mean square 7.5
+        let rms = 7.5f32.sqrt();
+        let expected = [1. / rms, 2. / rms, 3. / rms, 4. / rms];
+        let rms_normed = rms_norm(&x, &ones, 0.0).unwrap();
+        rms_normed
+            .all_close(&CPUTensor::from_slice(&expected, shape![1, 4]), 1e-6, 1e-6)
+            .unwrap();
+        assert!(layer_norm(&x, &CPUTensor::zeros::<f32>(shape![3]), &zeros, 0.0).is_err());
+
+        //[2, 3] @ [3, 2], then the same product from transposed operands
+        let a = CPUTensor::from_slice(&[1f32, 2., 3., 4., 5., 6.], shape![1, 2, 3]);
+        let b = CPUTensor::from_slice(&[7f32, 8., 9., 10., 11., 12.], shape![1, 3, 2]);
+        let a_t = CPUTensor::from_slice(&[1f32, 4., 2., 5., 3., 6.], shape![1, 3, 2]);
+        let b_t = CPUTensor::from_slice(&[7f32, 9., 11., 8., 10., 12.], shape![1, 2, 3]);
+        let expected = CPUTensor::from_slice(&[58f32, 64., 139., 154.], shape![1, 2, 2]);
+        matmul(&a, &b, false, false)
+            .unwrap()
+            .all_close(&expected, 0.0, 0.0)
+            .unwrap();
+        matmul(&a_t, &b_t, true, true)
+            .unwrap()
+            .all_close(&expected, 0.0, 0.0)
+            .unwrap();
+        assert!(matmul(&a, &a, false, false).is_err());
+
+        let a = CPUTensor::randn::<f32>(shape![2, 8, 16]);
+        let b = CPUTensor::randn::<f32>(shape![2, 16, 32]);
+        let quantizer = Quantizer::new(Quantization::SInt8);
+        let quantized = quantizer.quantize(b.clone());
+        let dequantized = quantizer.dequantize(quantized.clone());
+        qmatmul(&a, &quantized)
+            .unwrap()
+            .all_close(&matmul(&a, &dequantized, false, false).unwrap(), 0.0, 0.0)
+            .unwrap();
+
+        //Position 0 is untouched, position 1 rotates pair 0 by 1 radian and pair 1 by 0.01
+        let input = CPUTensor::from_slice(&[1f32, 0., 1., 0., 1., 0., 1., 0.], shape![1, 2, 4]);
+        let (s0, c0, s1, c1) = (1f32.sin(), 1f32.cos(), 0.01f32.sin(), 0.01f32.cos());
+        let half_split = [1., 0., 1., 0., c0 - s0, 0., s0 + c0, 0.];
+        let interleaved = [1., 0., 1., 0., c0, s0, c1, s1];
+        rope(&input, RopeStyle::HalfSplit, 4, 10000.0, 0)
+            .unwrap()
+            .all_close(
+                &CPUTensor::from_slice(&half_split, shape![1, 2, 4]),
+                1e-6,
+                1e-6,
+            )
+            .unwrap();
+        rope(&input, RopeStyle::Interleaved, 4, 10000.0, 0)
+            .unwrap()
+            .all_close(
+                &CPUTensor::from_slice(&interleaved, shape![1, 2, 4]),
+                1e-6,
+                1e-6,
+            )
+            .unwrap();
+        //With dim 2 only the first pair rotates
+        let partial = rope(&input, RopeStyle::Interleaved, 2, 10000.0, 0).unwrap();
+        assert_eq!(partial.to_vec::<f32>().unwrap()[4..], [c0, s0, 1., 0.]);
+    }
+}
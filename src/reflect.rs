@@ -0,0 +1,427 @@
+use std::fmt::Display;
+
+use naga::{
+    valid::{FunctionInfo, ModuleInfo},
+    AddressSpace, ArraySize, Module, ScalarKind, StorageAccess, TypeInner,
+};
+
+use crate::{CPUTensor, DType, MetaMember, OpMetadata, StructLayout};
+
+/// How a [`ShaderBinding`] is declared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingKind {
+    Storage {
+        read_only: bool,
+    },
+    Uniform,
+    /// Textures, samplers and anything else no tensor can be bound to.
+    Other,
+}
+
+impl Display for BindingKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BindingKind::Storage { read_only: true } => write!(f, "storage, read"),
+            BindingKind::Storage { read_only: false } => write!(f, "storage, read_write"),
+            BindingKind::Uniform => write!(f, "uniform"),
+            BindingKind::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// A resource variable declared by a shader, e.g.
+/// `@group(0) @binding(3) C: array<vec4<f32>> (storage, read_write)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShaderBinding {
+    pub group: u32,
+    pub binding: u32,
+    pub name: String,
+    pub kind: BindingKind,
+    pub ty: String,
+    /// The scalar the type is built from, if a tensor could hold it.
+    pub element: Option<DType>,
+    /// Whether `main` uses it. Unused bindings are left out of the derived layout.
+    pub used: bool,
+}
+
+impl ShaderBinding {
+    fn slot(&self) -> String {
+        format!("@group({}) @binding({})", self.group, self.binding)
+    }
+}
+
+impl Display for ShaderBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}: {} ({})",
+            self.slot(),
+            self.name,
+            self.ty,
+            self.kind
+        )?;
+        if !self.used {
+            write!(f, ", unused")?;
+        }
+        Ok(())
+    }
+}
+
+fn type_name(module: &Module, ty: naga::Handle<naga::Type>) -> String {
+    let scalar = |s: naga::Scalar| match s.kind {
+        ScalarKind::Float => format!("f{}", s.width * 8),
+        ScalarKind::Sint => format!("i{}", s.width * 8),
+        ScalarKind::Uint => format!("u{}", s.width * 8),
+        ScalarKind::Bool => "bool".to_string(),
+        kind => format!("{:?}", kind),
+    };
+    let ty = &module.types[ty];
+    match &ty.inner {
+        TypeInner::Scalar(s) => scalar(*s),
+        TypeInner::Vector { size, scalar: s } => format!("vec{}<{}>", *size as u8, scalar(*s)),
+        TypeInner::Matrix {
+            columns,
+            rows,
+            scalar: s,
+        } => format!("mat{}x{}<{}>", *columns as u8, *rows as u8, scalar(*s)),
+        TypeInner::Atomic(s) => format!("atomic<{}>", scalar(*s)),
+        TypeInner::Array {
+            base,
+            size: ArraySize::Constant(n),
+            ..
+        } => format!("array<{}, {}>", type_name(module, *base), n),
+        TypeInner::Array { base, .. } => format!("array<{}>", type_name(module, *base)),
+        inner => ty.name.clone().unwrap_or_else(|| format!("{:?}", inner)),
+    }
+}
+
+//The tensor dtype matching the innermost scalar of arrays and vectors
+fn element(module: &Module, ty: naga::Handle<naga::Type>) -> Option<DType> {
+    let scalar = match &module.types[ty].inner {
+        TypeInner::Scalar(s) | TypeInner::Atomic(s) => *s,
+        TypeInner::Vector { scalar, .. } | TypeInner::Matrix { scalar, .. } => *scalar,
+        TypeInner::Array { base, .. } => return element(module, *base),
+        _ => return None,
+    };
+    match (scalar.kind, scalar.width) {
+        (ScalarKind::Float, 4) => Some(DType::F32),
+        (ScalarKind::Float, 2) => Some(DType::F16),
+        (ScalarKind::Sint, 4) => Some(DType::I32),
+        (ScalarKind::Uint, 4) => Some(DType::U32),
+        _ => None,
+    }
+}
+
+fn entry_point<'a>(module: &Module, info: &'a ModuleInfo) -> anyhow::Result<&'a FunctionInfo> {
+    module
+        .entry_points
+        .iter()
+        .position(|ep| ep.name == "main")
+        .map(|i| info.get_entry_point(i))
+        .ok_or_else(|| anyhow::anyhow!("Shader has no entry point named main"))
+}
+
+/// Lists the resource bindings of a validated module, e.g. from [`KernelSource::module`],
+/// sorted by group and binding.
+pub fn reflect_bindings(module: &Module, info: &ModuleInfo) -> anyhow::Result<Vec<ShaderBinding>> {
+    let main = entry_point(module, info)?;
+
+    let mut bindings = module
+        .global_variables
+        .iter()
+        .filter_map(|(handle, var)| {
+            let binding = var.binding.as_ref()?;
+            let kind = match var.space {
+                AddressSpace::Storage { access } => BindingKind::Storage {
+                    read_only: !access.contains(StorageAccess::STORE),
+                },
+                AddressSpace::Uniform => BindingKind::Uniform,
+                _ => BindingKind::Other,
+            };
+            Some(ShaderBinding {
+                group: binding.group,
+                binding: binding.binding,
+                name: var.name.clone().unwrap_or_default(),
+                kind,
+                ty: type_name(module, var.ty),
+                element: element(module, var.ty),
+                used: !main[handle].is_empty(),
+            })
+        })
+        .collect::<Vec<_>>();
+    bindings.sort_by_key(|b| (b.group, b.binding));
+    Ok(bindings)
+}
+
+/// Reflects the struct of the uniform `main` reads its metadata from, i.e. the uniform in
+/// the last bind group.
+pub fn reflect_metadata(module: &Module, info: &ModuleInfo) -> anyhow::Result<StructLayout> {
+    let main = entry_point(module, info)?;
+    let uniform = module
+        .global_variables
+        .iter()
+        .filter(|(handle, var)| var.space == AddressSpace::Uniform && !main[*handle].is_empty())
+        .filter_map(|(_, var)| Some((var.binding.as_ref()?.group, var.ty)))
+        .max_by_key(|(group, _)| *group);
+    let Some((_, ty)) = uniform else {
+        anyhow::bail!("Shader has no uniform for its metadata");
+    };
+    let TypeInner::Struct { members, span } = &module.types[ty].inner else {
+        anyhow::bail!(
+            "Metadata uniform is a {}, not a struct",
+            type_name(module, ty)
+        );
+    };
+    let members = members
+        .iter()
+        .map(|m| MetaMember {
+            name: m.name.clone().unwrap_or_default(),
+            ty: type_name(module, m.ty),
+            offset: m.offset as u64,
+            size: module.types[m.ty].inner.size(module.to_ctx()) as u64,
+        })
+        .collect();
+    Ok(StructLayout {
+        name: module.types[ty].name.clone().unwrap_or_default(),
+        members,
+        size: *span as u64,
+    })
+}
+
+/// Checks the layout of `M` against the uniform struct the shader reads it as.
+/// Metadata without a [`OpMetadata::layout`] is not checked.
+pub fn check_metadata<M: OpMetadata>(module: &Module, info: &ModuleInfo) -> anyhow::Result<()> {
+    let Some(layout) = M::layout() else {
+        return Ok(());
+    };
+    //Catches a WGSL alignment in `WgslType` disagreeing with what encase writes
+    let written = M::min_size().get();
+    if layout.size != written {
+        anyhow::bail!(
+            "{} is laid out as {} bytes, but encase writes {} bytes",
+            layout.name,
+            layout.size,
+            written
+        );
+    }
+    layout.compare(&reflect_metadata(module, info)?)
+}
+
+//A tensor segment as bound by `tensors_to_bind_groups`
+struct TensorSlot {
+    group: u32,
+    binding: u32,
+    tensor: usize,
+    element: DType,
+}
+
+impl TensorSlot {
+    fn describe(&self) -> String {
+        format!(
+            "Tensor {} ({:?}) is bound to @group({}) @binding({})",
+            self.tensor, self.element, self.group, self.binding
+        )
+    }
+}
+
+//Element types of the buffer segments a tensor of this dtype is bound as
+fn segment_elements(dt: DType) -> Vec<DType> {
+    match dt {
+        DType::WQ8 => vec![DType::U32, DType::F32],
+        dt => vec![dt],
+    }
+}
+
+/// Reflects the bindings of a validated module and checks them against the tensors it will
+/// be dispatched with, before wgpu derives a layout from them.
+///
+/// Tensors are bound in order, one binding per buffer segment and four bindings per group,
+/// followed by the metadata uniform at binding 0 of the next group.
+pub fn check_bindings(
+    module: &Module,
+    info: &ModuleInfo,
+    tensors: &[CPUTensor],
+) -> anyhow::Result<Vec<ShaderBinding>> {
+    let bindings = reflect_bindings(module, info)?;
+    let elements = tensors
+        .iter()
+        .enumerate()
+        .flat_map(|(i, t)| segment_elements(t.dt()).into_iter().map(move |e| (i, e)));
+    let slots = elements
+        .enumerate()
+        .map(|(idx, (tensor, element))| TensorSlot {
+            group: (idx / 4) as u32,
+            binding: (idx % 4) as u32,
+            tensor,
+            element,
+        })
+        .collect::<Vec<_>>();
+    let find = |group, binding| {
+        bindings
+            .iter()
+            .find(|b| b.group == group && b.binding == binding)
+    };
+
+    for slot in &slots {
+        let Some(binding) = find(slot.group, slot.binding) else {
+            anyhow::bail!("{}, which the shader does not declare", slot.describe());
+        };
+        if !binding.used {
+            anyhow::bail!(
+                "{} `{}`, which main never uses, so the derived layout leaves it out",
+                slot.describe(),
+                binding.name
+            );
+        }
+        if !matches!(binding.kind, BindingKind::Storage { .. }) {
+            anyhow::bail!("{}, but the shader declares {}", slot.describe(), binding);
+        }
+        if binding.element.is_some_and(|e| e != slot.element) {
+            anyhow::bail!("{}, but the shader declares {}", slot.describe(), binding);
+        }
+    }
+
+    let uniform_group = slots.len().div_ceil(4) as u32;
+    match find(uniform_group, 0) {
+        Some(b) if b.kind == BindingKind::Uniform && b.used => {}
+        Some(b) => anyhow::bail!(
+            "The metadata is bound to @group({}) @binding(0), but the shader declares {}",
+            uniform_group,
+            b
+        ),
+        None => anyhow::bail!(
+            "The metadata is bound to @group({}) @binding(0), which the shader does not declare",
+            uniform_group
+        ),
+    }
+
+    let is_bound = |b: &ShaderBinding| {
+        (b.group, b.binding) == (uniform_group, 0)
+            || slots
+                .iter()
+                .any(|s| (s.group, s.binding) == (b.group, b.binding))
+    };
+    if let Some(b) = bindings.iter().find(|b| b.used && !is_bound(b)) {
+        anyhow::bail!(
+            "No tensor is bound to {}, {} tensors fill {} bindings",
+            b,
+            tensors.len(),
+            slots.len()
+        );
+    }
+    Ok(bindings)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    const SOURCE: &str = r#"
+@group(0) @binding(0)
+var<storage, read> X: array<vec4<f32>>;
+
+@group(0) @binding(1)
+var<storage, read_write> Y: array<f32>;
+
+struct Meta {
+    numel: u32,
+}
+
+@group(1) @binding(0)
+var<uniform> metadata: Meta;
+
+@compute @workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index < metadata.numel) {
+        Y[index] = dot(X[index], vec4<f32>(1.0));
+    }
+}
+"#;
+
+    #[test]
+    pub fn binding_reflection() {
+        let (module, info) = KernelSource::from(SOURCE.to_string()).module().unwrap();
+        let bindings = reflect_bindings(&module, &info).unwrap();
+        let listed = bindings.iter().map(|b| b.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            listed,
+            [
+                "@group(0) @binding(0) X: array<vec4<f32>> (storage, read)",
+                "@group(0) @binding(1) Y: array<f32> (storage, read_write)",
+                "@group(1) @binding(0) metadata: Meta (uniform)",
+            ]
+        );
+
+        let x = CPUTensor::zeros::<f32>(shape![16]);
+        let y = CPUTensor::zeros::<f32>(shape![4]);
+        assert!(check_bindings(&module, &info, &[x.clone(), y.clone()]).is_ok());
+
+        let missing = check_bindings(&module, &info, std::slice::from_ref(&x)).unwrap_err();
+        assert!(missing.to_string().contains("@group(0) @binding(1) Y"));
+
+        let wrong_type = CPUTensor::zeros::<u32>(shape![4]);
+        let mismatched = check_bindings(&module, &info, &[x.clone(), wrong_type]).unwrap_err();
+        assert!(mismatched.to_string().starts_with("Tensor 1 (U32)"));
+
+        let extra = check_bindings(&module, &info, &[x, y.clone(), y]).unwrap_err();
+        assert!(extra.to_string().contains("@group(0) @binding(2)"));
+    }
+
+    #[test]
+    pub fn registered_bindings() {
+        for registered in kernels::registry().iter() {
+            let kernel = registered.build(&Params::default()).unwrap();
+            if let Err(e) = kernel.bindings() {
+                panic!("{}: {:#}", registered.name, e);
+            }
+        }
+    }
+
+    op_metadata! {
+        struct StridedMeta {
+            strides: glam::UVec3,
+            numel: u32,
+            scale: f32,
+        }
+    }
+
+    #[test]
+    pub fn metadata_layout() {
+        let shader = |strides: &str| {
+            let source = KernelSource::from(format!(
+                "struct Meta {{ strides: {}, numel: u32, scale: f32 }}
+                @group(0) @binding(0) var<uniform> metadata: Meta;
+                @compute @workgroup_size(1) fn main() {{ let n = metadata.numel; }}",
+                strides
+            ));
+            source.module().unwrap()
+        };
+        let layout = StridedMeta::layout().unwrap();
+        let (module, info) = shader("vec3<u32>");
+        let reflected = reflect_metadata(&module, &info).unwrap();
+        assert_eq!(layout.members, reflected.members);
+        assert_eq!((layout.size, reflected.size), (32, 32));
+        let meta = StridedMeta {
+            strides: glam::UVec3::ONE,
+            numel: 1,
+            scale: 1.0,
+        };
+        assert_eq!(encase::ShaderType::size(&meta).get(), layout.size);
+        assert!(check_metadata::<StridedMeta>(&module, &info).is_ok());
+
+        let (module, info) = shader("vec4<u32>");
+        let drifted = check_metadata::<StridedMeta>(&module, &info).unwrap_err();
+        assert_eq!(
+            drifted.to_string(),
+            "StridedMeta.strides is vec3<u32>, but vec4<u32> in the shader's Meta"
+        );
+
+        //Structs of scalars are only as aligned as their members
+        let mut context = tera::Context::new();
+        context.insert_workload(&Workload::new(wgs![256, 1, 1], wgc![1, 1, 1]));
+        let bandwidth = KernelSource::render(wgsl_template!("roofline/bandwidth.wgsl"), &context);
+        let (module, info) = bandwidth.module().unwrap();
+        assert!(check_metadata::<PeakMeta>(&module, &info).is_ok());
+    }
+}
@@ -0,0 +1,166 @@
+use naga::valid::Capabilities;
+
+use crate::{
+    dispatch_validate, measure, preflight, CPUTensor, Cost, Dispatches, GPUHandle, GPUTensor,
+    Golden, KernelBench, KernelSource, Params, PreparedKernel, ShaderBinding, WgpuTimer,
+};
+
+/// Object safe view of a [`KernelBench`], so kernels of different types can be registered
+/// together, compared on shared tensors and run without criterion.
+pub trait Runnable {
+    fn params(&self) -> Params;
+    fn tensors(&self) -> Vec<CPUTensor>;
+    fn cost(&self, tensors: &[CPUTensor]) -> Cost;
+    fn dispatches(&self) -> Dispatches;
+    /// The shader as rendered for the kernel's own tensors.
+    fn shader(&self) -> KernelSource;
+    /// Bindings of the rendered shader, checked against the kernel's tensors and metadata.
+    fn bindings(&self) -> anyhow::Result<Vec<ShaderBinding>>;
+    fn validate(&self, handle: &GPUHandle) -> anyhow::Result<()>;
+    /// Compiles the kernel and binds it to `tensors`, which need not be its own.
+    fn prepare(&self, handle: &GPUHandle, tensors: &[CPUTensor]) -> PreparedKernel;
+    /// Dispatches the kernel once on `tensors`, returning them as the kernel left them.
+    fn outputs(&self, handle: &GPUHandle, tensors: &[CPUTensor]) -> Vec<GPUTensor>;
+    fn record_golden(&self, seed: u64) -> anyhow::Result<Option<Golden>>;
+    /// Nanoseconds per dispatch of each of the `samples` measurements.
+    fn measure(&self, timer: &WgpuTimer, samples: usize) -> Vec<f64>;
+}
+
+impl<K: KernelBench> Runnable for K {
+    fn params(&self) -> Params {
+        KernelBench::params(self)
+    }
+
+    fn tensors(&self) -> Vec<CPUTensor> {
+        KernelBench::tensors(self)
+    }
+
+    fn cost(&self, tensors: &[CPUTensor]) -> Cost {
+        KernelBench::cost(self, tensors)
+    }
+
+    fn dispatches(&self) -> Dispatches {
+        KernelBench::dispatches(self)
+    }
+
+    fn shader(&self) -> KernelSource {
+        self.source(&self.workload(&KernelBench::tensors(self)))
+    }
+
+    fn bindings(&self) -> anyhow::Result<Vec<ShaderBinding>> {
+        let tensors = KernelBench::tensors(self);
+        let source = self.source(&self.workload(&tensors));
+        //Checked against the device's capabilities when the kernel is prepared
+        preflight::<K>(&source, &tensors, Capabilities::all())
+    }
+
+    fn validate(&self, handle: &GPUHandle) -> anyhow::Result<()> {
+        //Reported as an error rather than the panic of dispatching a mismatched kernel
+        Runnable::bindings(self)?;
+        KernelBench::validate(self, handle, &KernelBench::tensors(self))
+    }
+
+    fn prepare(&self, handle: &GPUHandle, tensors: &[CPUTensor]) -> PreparedKernel {
+        PreparedKernel::new(handle, self, tensors)
+    }
+
+    fn outputs(&self, handle: &GPUHandle, tensors: &[CPUTensor]) -> Vec<GPUTensor> {
+        dispatch_validate(handle, self, tensors)
+    }
+
+    fn record_golden(&self, seed: u64) -> anyhow::Result<Option<Golden>> {
+        KernelBench::record_golden(self, seed)
+    }
+
+    fn measure(&self, timer: &WgpuTimer, samples: usize) -> Vec<f64> {
+        measure(timer, self, samples)
+    }
+}
+
+type Builder = Box<dyn Fn(&Params) -> anyhow::Result<Box<dyn Runnable>>>;
+
+/// A kernel that can be built by name, with default parameters that may be overridden.
+pub struct RegisteredKernel {
+    pub name: String,
+    pub description: String,
+    pub defaults: Params,
+    build: Builder,
+}
+
+impl RegisteredKernel {
+    /// Builds the kernel from its defaults, with every parameter in `overrides` replaced.
+    pub fn build(&self, overrides: &Params) -> anyhow::Result<Box<dyn Runnable>> {
+        if let Some((key, _)) = overrides.iter().find(|(k, _)| !self.defaults.contains(k)) {
+            anyhow::bail!(
+                "Unknown parameter {} for {}, expected one of {}",
+                key,
+                self.name,
+                self.defaults
+            );
+        }
+        (self.build)(&self.defaults.merge(overrides))
+    }
+}
+
+/// # Registry
+///
+/// Kernels runnable by name, e.g. from the `wgpu-bench` binary.
+#[derive(Default)]
+pub struct Registry {
+    kernels: Vec<RegisteredKernel>,
+}
+
+impl Registry {
+    pub fn register<K: KernelBench + 'static>(
+        mut self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        defaults: Params,
+        build: impl Fn(&Params) -> anyhow::Result<K> + 'static,
+    ) -> Self {
+        self.kernels.push(RegisteredKernel {
+            name: name.into(),
+            description: description.into(),
+            defaults,
+            build: Box::new(move |params| Ok(Box::new(build(params)?))),
+        });
+        self
+    }
+
+    /// Looks up a kernel by name, ignoring case.
+    pub fn get(&self, name: &str) -> anyhow::Result<&RegisteredKernel> {
+        self.kernels
+            .iter()
+            .find(|k| k.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let names = self.kernels.iter().map(|k| k.name.as_str());
+                anyhow::anyhow!(
+                    "No kernel named {}, expected one of: {}",
+                    name,
+                    names.collect::<Vec<_>>().join(", ")
+                )
+            })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RegisteredKernel> {
+        self.kernels.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn registry_overrides() {
+        let registry = kernels::registry();
+        let sgemm = registry.get("SGEMM").unwrap();
+        let kernel = sgemm.build(&params![M = 512]).unwrap();
+        assert_eq!(kernel.params().to_string(), "B=1,M=512,N=1024,K=1024");
+
+        assert!(sgemm.build(&params![X = 1]).is_err());
+        assert!(sgemm.build(&params![M = "big"]).is_err());
+        assert!(registry.get("layernorm/welfordvectorized").is_ok());
+        assert!(registry.get("conv2d").is_err());
+    }
+}
@@ -0,0 +1,289 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use crate::{output_dir, read_records, welch_t_test, write_records, BenchRecord, SampleSummary};
+
+//Identifies a measurement across runs: kernel, parameters and adapter fingerprint
+fn key(record: &BenchRecord) -> String {
+    format!(
+        "{}/{}@{}",
+        record.kernel, record.params, record.adapter.fingerprint
+    )
+}
+
+fn summary(record: &BenchRecord) -> SampleSummary {
+    SampleSummary::new(record.samples, record.mean_ns, record.stddev_ns)
+}
+
+/// # Baseline
+///
+/// A named set of results that later runs are compared against, stored as JSON in
+/// `target/wgpu-bench/baselines/<name>.json`.
+#[derive(Debug, Clone, Default)]
+pub struct Baseline {
+    records: BTreeMap<String, BenchRecord>,
+}
+
+impl Baseline {
+    /// Path of the baseline called `name`, or `name` itself if it is a `.json` file.
+    pub fn path(name: &str) -> PathBuf {
+        if name.ends_with(".json") {
+            PathBuf::from(name)
+        } else {
+            output_dir()
+                .join("baselines")
+                .join(format!("{}.json", name))
+        }
+    }
+
+    pub fn from_records(records: impl IntoIterator<Item = BenchRecord>) -> Self {
+        let mut baseline = Self::default();
+        baseline.extend(records);
+        baseline
+    }
+
+    /// Adds records, replacing any with the same kernel, parameters and adapter.
+    pub fn extend(&mut self, records: impl IntoIterator<Item = BenchRecord>) {
+        for record in records {
+            self.records.insert(key(&record), record);
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self::from_records(read_records(path).map_err(|e| {
+            anyhow::anyhow!("Failed to read baseline {}: {}", path.display(), e)
+        })?))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        write_records(path, &self.records().cloned().collect::<Vec<_>>())
+    }
+
+    pub fn get(&self, record: &BenchRecord) -> Option<&BenchRecord> {
+        self.records.get(&key(record))
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &BenchRecord> {
+        self.records.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+/// Outcome of comparing one result to its baseline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Regressed,
+    Improved,
+    Unchanged,
+    /// No baseline for this kernel, parameters and adapter.
+    Missing,
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let verdict = match self {
+            Verdict::Regressed => "REGRESSED",
+            Verdict::Improved => "improved",
+            Verdict::Unchanged => "unchanged",
+            Verdict::Missing => "no baseline",
+        };
+        write!(f, "{}", verdict)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RegressionCheck {
+    pub key: String,
+    pub baseline_ns: Option<f64>,
+    pub current_ns: f64,
+    pub change: f64,  //Relative change of the mean, positive is slower
+    pub p_value: f64, //Of the change in the direction observed
+    pub verdict: Verdict,
+}
+
+impl Display for RegressionCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.baseline_ns {
+            Some(baseline) => write!(
+                f,
+                "{:<11} {}: {:.4} ns -> {:.4} ns ({:+.2}%, p = {:.4})",
+                self.verdict.to_string(),
+                self.key,
+                baseline,
+                self.current_ns,
+                100.0 * self.change,
+                self.p_value
+            ),
+            None => write!(
+                f,
+                "{:<11} {}: {:.4} ns",
+                self.verdict.to_string(),
+                self.key,
+                self.current_ns
+            ),
+        }
+    }
+}
+
+/// # RegressionGate
+///
+/// A result regresses when its mean is more than `tolerance` slower than the baseline and
+/// Welch's t-test finds the slowdown significant at `alpha`, so noisy runs don't fail the gate.
+#[derive(Debug, Clone, Copy, derive_new::new)]
+pub struct RegressionGate {
+    pub tolerance: f64,
+    pub alpha: f64,
+}
+
+impl Default for RegressionGate {
+    fn default() -> Self {
+        Self::new(0.05, 0.05)
+    }
+}
+
+impl RegressionGate {
+    pub fn check(&self, baseline: &Baseline, current: &[BenchRecord]) -> RegressionReport {
+        let checks = current
+            .iter()
+            .map(|record| self.check_record(baseline.get(record), record))
+            .collect();
+        RegressionReport { checks }
+    }
+
+    fn check_record(
+        &self,
+        baseline: Option<&BenchRecord>,
+        current: &BenchRecord,
+    ) -> RegressionCheck {
+        let Some(baseline) = baseline else {
+            return RegressionCheck {
+                key: key(current),
+                baseline_ns: None,
+                current_ns: current.mean_ns,
+                change: 0.0,
+                p_value: f64::NAN,
+                verdict: Verdict::Missing,
+            };
+        };
+        let change = current.mean_ns / baseline.mean_ns - 1.0;
+        let (before, after) = (summary(baseline), summary(current));
+        let p_value = match change > 0.0 {
+            true => welch_t_test(before, after),
+            false => welch_t_test(after, before),
+        };
+        //Without enough samples for the test, fall back to the tolerance alone
+        let significant = p_value.is_nan() || p_value < self.alpha;
+        let verdict = if significant && change > self.tolerance {
+            Verdict::Regressed
+        } else if significant && change < -self.tolerance {
+            Verdict::Improved
+        } else {
+            Verdict::Unchanged
+        };
+        RegressionCheck {
+            key: key(current),
+            baseline_ns: Some(baseline.mean_ns),
+            current_ns: current.mean_ns,
+            change,
+            p_value,
+            verdict,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RegressionReport {
+    pub checks: Vec<RegressionCheck>,
+}
+
+impl RegressionReport {
+    pub fn regressions(&self) -> impl Iterator<Item = &RegressionCheck> {
+        self.checks
+            .iter()
+            .filter(|c| c.verdict == Verdict::Regressed)
+    }
+
+    pub fn passed(&self) -> bool {
+        self.regressions().next().is_none()
+    }
+}
+
+impl Display for RegressionReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for check in &self.checks {
+            writeln!(f, "{}", check)?;
+        }
+        let regressions = self.regressions().count();
+        match regressions {
+            0 => write!(f, "No regressions in {} results", self.checks.len()),
+            n => write!(f, "{} of {} results regressed", n, self.checks.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn record(kernel: &str, samples: &[f64]) -> BenchRecord {
+        let adapter = AdapterRecord {
+            name: "Apple M3 Max".to_string(),
+            backend: "Metal".to_string(),
+            driver: String::new(),
+            fingerprint: "Apple_M3_Max-Metal-0000-0000-".to_string(),
+        };
+        let cost = Cost::new(0, 1_000_000);
+        BenchRecord::new(kernel, params![M = 1024], adapter, samples, cost, false)
+    }
+
+    #[test]
+    pub fn regression_gate() {
+        let dir = TempDir::new("regression-gate");
+        let (baseline_path, current_path) = (dir.join("main.json"), dir.join("current.json"));
+        let stable = [100.0, 101.0, 99.0, 100.5, 99.5, 100.0, 100.2, 99.8];
+        let slower = stable.map(|s| s * 1.2);
+        let noisy = [60.0, 150.0, 90.0, 140.0, 70.0, 160.0, 80.0, 130.0];
+
+        let baseline = Baseline::from_records(["A", "B", "C", "D"].map(|k| record(k, &stable)));
+        baseline.save(&baseline_path).unwrap();
+        let current = vec![
+            record("A", &stable),
+            record("B", &slower),
+            record("C", &noisy),
+            record("D", &stable.map(|s| s * 0.8)),
+            record("E", &stable),
+        ];
+        write_records(&current_path, &current).unwrap();
+
+        let baseline = Baseline::load(&baseline_path).unwrap();
+        let current = read_records(&current_path).unwrap();
+        let report = RegressionGate::default().check(&baseline, &current);
+        let verdicts = report.checks.iter().map(|c| c.verdict).collect::<Vec<_>>();
+        assert_eq!(
+            verdicts,
+            [
+                Verdict::Unchanged,
+                Verdict::Regressed,
+                Verdict::Unchanged,
+                Verdict::Improved,
+                Verdict::Missing
+            ]
+        );
+        assert!(!report.passed());
+
+        //A looser tolerance lets the slowdown through
+        assert!(RegressionGate::new(0.25, 0.05)
+            .check(&baseline, &current)
+            .passed());
+    }
+}
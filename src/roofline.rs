@@ -0,0 +1,567 @@
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    format_scaled, measure, op_metadata, output_dir, shape, wgc, wgs, wgsl_template, CPUTensor,
+    Cost, Dispatches, GPUHandle, KernelBench, KernelContextExt, KernelSource, WgpuTimer, Workload,
+    BYTE_UNITS, FLOP_UNITS,
+};
+
+/// Where [`DevicePeaks`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeakSource {
+    Configured,
+    Measured,
+}
+
+impl std::fmt::Display for PeakSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeakSource::Configured => write!(f, "configured"),
+            PeakSource::Measured => write!(f, "measured"),
+        }
+    }
+}
+
+/// Peak global memory bandwidth and compute throughput of a device.
+#[derive(Debug, Clone, Copy, PartialEq, derive_new::new)]
+pub struct DevicePeaks {
+    pub bandwidth: f64, //Bytes per second
+    pub flops: f64,     //FLOPs per second
+    pub source: PeakSource,
+}
+
+impl DevicePeaks {
+    pub const BANDWIDTH_VAR: &'static str = "WGPU_BENCH_PEAK_GBPS";
+    pub const FLOPS_VAR: &'static str = "WGPU_BENCH_PEAK_GFLOPS";
+    /// Set to measure the peaks when they are not configured.
+    pub const MEASURE_VAR: &'static str = "WGPU_BENCH_ROOFLINE";
+
+    /// Peaks configured in GB/s and GFLOP/s through the environment.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let var = |name: &str| -> anyhow::Result<Option<f64>> {
+            match std::env::var(name) {
+                Ok(value) => {
+                    Ok(Some(value.trim().parse::<f64>().map_err(|e| {
+                        anyhow::anyhow!("Invalid {}={:?}: {}", name, value, e)
+                    })?))
+                }
+                Err(_) => Ok(None),
+            }
+        };
+        match (var(Self::BANDWIDTH_VAR)?, var(Self::FLOPS_VAR)?) {
+            (Some(gbps), Some(gflops)) => Ok(Some(Self::new(
+                gbps * 1e9,
+                gflops * 1e9,
+                PeakSource::Configured,
+            ))),
+            (None, None) => Ok(None),
+            _ => anyhow::bail!(
+                "Both {} and {} must be set",
+                Self::BANDWIDTH_VAR,
+                Self::FLOPS_VAR
+            ),
+        }
+    }
+
+    /// Measures the peaks with a streaming copy and an FMA chain kernel.
+    /// Takes the fastest sample of each, as a peak is the best the device can do.
+    pub fn measure(timer: &WgpuTimer) -> Self {
+        const SAMPLES: usize = 10;
+        let rate = |kernel: PeakKernel| {
+            let tensors = kernel.tensors();
+            let cost = kernel.cost(&tensors);
+            let fastest = measure(timer, &kernel, SAMPLES)
+                .into_iter()
+                .fold(f64::INFINITY, f64::min);
+            (cost, fastest)
+        };
+        let (copy, copy_ns) = rate(PeakKernel::Bandwidth);
+        let (fma, fma_ns) = rate(PeakKernel::Flops);
+        let peaks = Self::new(
+            copy.bytes as f64 * 1e9 / copy_ns,
+            fma.flops as f64 * 1e9 / fma_ns,
+            PeakSource::Measured,
+        );
+        log::info!("Measured device peaks: {}", peaks);
+        peaks
+    }
+
+    /// Configured peaks if present, otherwise measured peaks if requested through
+    /// `WGPU_BENCH_ROOFLINE`. Roofline analysis is disabled when this returns None.
+    pub fn resolve(timer: &WgpuTimer) -> Option<Self> {
+        match Self::from_env() {
+            Ok(Some(peaks)) => return Some(peaks),
+            Ok(None) => {}
+            Err(e) => log::warn!("Ignoring configured device peaks: {:?}", e),
+        }
+        std::env::var_os(Self::MEASURE_VAR).map(|_| Self::measure(timer))
+    }
+
+    /// Arithmetic intensity at which a kernel stops being memory-bound.
+    pub fn ridge_point(&self) -> f64 {
+        self.flops / self.bandwidth
+    }
+
+    /// Attainable FLOP/s at a given arithmetic intensity.
+    pub fn attainable(&self, intensity: f64) -> f64 {
+        self.flops.min(intensity * self.bandwidth)
+    }
+}
+
+impl std::fmt::Display for DevicePeaks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, {} (ridge {:.4} FLOP/B)",
+            format_scaled(self.bandwidth, &BYTE_UNITS),
+            format_scaled(self.flops, &FLOP_UNITS),
+            self.ridge_point()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Memory,
+    Compute,
+}
+
+impl std::fmt::Display for Bound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bound::Memory => write!(f, "memory-bound"),
+            Bound::Compute => write!(f, "compute-bound"),
+        }
+    }
+}
+
+/// A benchmarked kernel, placed on the roofline by its cost and mean dispatch time.
+#[derive(Debug, Clone, PartialEq, derive_new::new)]
+pub struct RooflinePoint {
+    pub name: String,
+    pub cost: Cost,
+    pub ns: f64,
+}
+
+impl RooflinePoint {
+    pub fn achieved_flops(&self) -> f64 {
+        self.cost.flops as f64 * 1e9 / self.ns
+    }
+
+    pub fn achieved_bandwidth(&self) -> f64 {
+        self.cost.bytes as f64 * 1e9 / self.ns
+    }
+
+    pub fn bound(&self, peaks: &DevicePeaks) -> Bound {
+        if self.cost.flops == 0 || self.cost.arithmetic_intensity() < peaks.ridge_point() {
+            Bound::Memory
+        } else {
+            Bound::Compute
+        }
+    }
+
+    /// Percentage of the attainable performance at this point's arithmetic intensity.
+    /// Kernels without FLOPs are measured against the peak bandwidth.
+    pub fn efficiency(&self, peaks: &DevicePeaks) -> f64 {
+        if self.cost.flops == 0 {
+            return 100.0 * self.achieved_bandwidth() / peaks.bandwidth;
+        }
+        let attainable = peaks.attainable(self.cost.arithmetic_intensity());
+        100.0 * self.achieved_flops() / attainable
+    }
+
+    pub fn summary(&self, peaks: &DevicePeaks) -> String {
+        format!(
+            "{:.1}% of attainable roofline, {} ({})",
+            self.efficiency(peaks),
+            self.bound(peaks),
+            self.cost.format_rates(self.ns)
+        )
+    }
+}
+
+/// # Roofline
+///
+/// Every kernel benchmarked on a device, placed against its peaks.
+/// Points are kept in `points.tsv` so that separate bench binaries share one plot, in a
+/// directory per adapter and [`PeakSource`] so that points are only drawn against their own roof.
+#[derive(Debug, Clone)]
+pub struct Roofline {
+    pub peaks: DevicePeaks,
+    pub points: Vec<RooflinePoint>,
+}
+
+impl Roofline {
+    const POINTS_FILE: &'static str = "points.tsv";
+    const PLOT_WIDTH: usize = 64;
+    const PLOT_HEIGHT: usize = 20;
+
+    pub fn new(peaks: DevicePeaks) -> Self {
+        Self {
+            peaks,
+            points: vec![],
+        }
+    }
+
+    /// `target/wgpu-bench/roofline/<adapter>/<peak source>/`.
+    pub fn dir(handle: &GPUHandle, peaks: &DevicePeaks) -> PathBuf {
+        output_dir()
+            .join("roofline")
+            .join(handle.fingerprint())
+            .join(peaks.source.to_string())
+    }
+
+    /// Loads the points previously saved in `dir`, skipping any that cannot be parsed.
+    pub fn load(dir: &Path, peaks: DevicePeaks) -> Self {
+        let mut roofline = Self::new(peaks);
+        let Ok(contents) = std::fs::read_to_string(dir.join(Self::POINTS_FILE)) else {
+            return roofline;
+        };
+        for line in contents.lines() {
+            let fields = line.split('\t').collect::<Vec<_>>();
+            let [name, flops, bytes, ns] = fields[..] else {
+                continue;
+            };
+            if let (Ok(flops), Ok(bytes), Ok(ns)) = (flops.parse(), bytes.parse(), ns.parse()) {
+                roofline.insert(RooflinePoint::new(
+                    name.to_string(),
+                    Cost::new(flops, bytes),
+                    ns,
+                ));
+            }
+        }
+        roofline
+    }
+
+    /// Adds a point, replacing any previous point with the same name.
+    pub fn insert(&mut self, point: RooflinePoint) {
+        match self.points.iter_mut().find(|p| p.name == point.name) {
+            Some(existing) => *existing = point,
+            None => self.points.push(point),
+        }
+    }
+
+    /// Writes the points along with a text and an SVG plot.
+    pub fn save(&self, dir: &Path) -> anyhow::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        let points = self
+            .points
+            .iter()
+            .map(|p| format!("{}\t{}\t{}\t{}\n", p.name, p.cost.flops, p.cost.bytes, p.ns))
+            .collect::<String>();
+        std::fs::write(dir.join(Self::POINTS_FILE), points)?;
+        std::fs::write(dir.join("roofline.txt"), self.to_text())?;
+        std::fs::write(dir.join("roofline.svg"), self.to_svg())?;
+        Ok(())
+    }
+
+    //Points that can be placed on log axes
+    fn plotted(&self) -> impl Iterator<Item = (usize, &RooflinePoint)> {
+        self.points
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.cost.flops > 0 && p.cost.bytes > 0)
+    }
+
+    //log10 extents of the intensity and FLOP/s axes
+    fn extents(&self) -> ((f64, f64), (f64, f64)) {
+        let ridge = self.peaks.ridge_point();
+        let (mut x_min, mut x_max) = (ridge, ridge);
+        let mut y_min = self.peaks.flops;
+        for (_, point) in self.plotted() {
+            x_min = x_min.min(point.cost.arithmetic_intensity());
+            x_max = x_max.max(point.cost.arithmetic_intensity());
+            y_min = y_min.min(point.achieved_flops());
+        }
+        let (x_min, x_max) = (x_min / 4.0, x_max * 4.0);
+        let y_min = y_min.min(self.peaks.attainable(x_min)) / 2.0;
+        let y_max = self.peaks.flops * 2.0;
+        (
+            (x_min.log10(), x_max.log10()),
+            (y_min.log10(), y_max.log10()),
+        )
+    }
+
+    fn label(index: usize) -> char {
+        (b'A' + (index % 26) as u8) as char
+    }
+
+    fn legend(&self) -> String {
+        let mut legend = String::new();
+        for (i, point) in self.points.iter().enumerate() {
+            let _ = writeln!(
+                legend,
+                "{}: {} {}",
+                Self::label(i),
+                point.name,
+                point.summary(&self.peaks)
+            );
+        }
+        legend
+    }
+
+    pub fn to_text(&self) -> String {
+        let (w, h) = (Self::PLOT_WIDTH, Self::PLOT_HEIGHT);
+        let ((x0, x1), (y0, y1)) = self.extents();
+        let row = |flops: f64| {
+            let y = (flops.log10() - y0) / (y1 - y0) * h as f64;
+            h - 1 - (y.floor().max(0.0) as usize).min(h - 1)
+        };
+        let col = |intensity: f64| {
+            let x = (intensity.log10() - x0) / (x1 - x0) * w as f64;
+            (x.floor().max(0.0) as usize).min(w - 1)
+        };
+
+        let mut grid = vec![vec![' '; w]; h];
+        let ridge = self.peaks.ridge_point();
+        let center = |c: usize| 10f64.powf(x0 + (c as f64 + 0.5) / w as f64 * (x1 - x0));
+        for (c, intensity) in (0..w).map(|c| (c, center(c))) {
+            let roof = if intensity < ridge { '/' } else { '-' };
+            grid[row(self.peaks.attainable(intensity))][c] = roof;
+        }
+        for (i, point) in self.plotted() {
+            let (r, c) = (
+                row(point.achieved_flops()),
+                col(point.cost.arithmetic_intensity()),
+            );
+            grid[r][c] = Self::label(i);
+        }
+
+        let top = format_scaled(10f64.powf(y1), &FLOP_UNITS);
+        let bottom = format_scaled(10f64.powf(y0), &FLOP_UNITS);
+        let margin = top.len().max(bottom.len());
+        let mut text = format!("Roofline: {}\n", self.peaks);
+        for (r, line) in grid.iter().enumerate() {
+            let axis = match r {
+                0 => top.as_str(),
+                r if r == h - 1 => bottom.as_str(),
+                _ => "",
+            };
+            let line = line.iter().collect::<String>();
+            let _ = writeln!(text, "{:>margin$} |{}", axis, line.trim_end());
+        }
+        let _ = writeln!(text, "{:>margin$} +{}", "", "-".repeat(w));
+        let left = format!("{:.4} FLOP/B", 10f64.powf(x0));
+        let right = format!("{:.4} FLOP/B", 10f64.powf(x1));
+        let _ = writeln!(
+            text,
+            "{:>margin$}  {}{:>width$}",
+            "",
+            left,
+            right,
+            width = w.saturating_sub(left.len())
+        );
+        text.push_str(&self.legend());
+        text
+    }
+
+    pub fn to_svg(&self) -> String {
+        const WIDTH: f64 = 720.0;
+        const HEIGHT: f64 = 440.0;
+        const MARGIN: f64 = 70.0;
+        let ((x0, x1), (y0, y1)) = self.extents();
+        let x =
+            |intensity: f64| MARGIN + (intensity.log10() - x0) / (x1 - x0) * (WIDTH - 2.0 * MARGIN);
+        let y = |flops: f64| {
+            HEIGHT - MARGIN - (flops.log10() - y0) / (y1 - y0) * (HEIGHT - 2.0 * MARGIN)
+        };
+        let escape = |s: &str| {
+            s.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+        };
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" font-family=\"monospace\" font-size=\"11\">\n"
+        );
+        let _ = writeln!(
+            svg,
+            "<text x=\"{MARGIN}\" y=\"20\">Roofline: {}</text>",
+            escape(&self.peaks.to_string())
+        );
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{MARGIN}\" y=\"{MARGIN}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#999\"/>",
+            WIDTH - 2.0 * MARGIN,
+            HEIGHT - 2.0 * MARGIN
+        );
+
+        let (lo, hi) = (10f64.powf(x0), 10f64.powf(x1));
+        let ridge = self.peaks.ridge_point();
+        let _ = writeln!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"#d62728\" stroke-width=\"2\" points=\"{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}\"/>",
+            x(lo),
+            y(self.peaks.attainable(lo)),
+            x(ridge),
+            y(self.peaks.flops),
+            x(hi),
+            y(self.peaks.flops)
+        );
+
+        for (i, point) in self.plotted() {
+            let (px, py) = (
+                x(point.cost.arithmetic_intensity()),
+                y(point.achieved_flops()),
+            );
+            let _ = writeln!(
+                svg,
+                "<circle cx=\"{px:.1}\" cy=\"{py:.1}\" r=\"4\" fill=\"#1f77b4\"><title>{}</title></circle>",
+                escape(&format!("{}: {}", point.name, point.summary(&self.peaks)))
+            );
+            let _ = writeln!(
+                svg,
+                "<text x=\"{:.1}\" y=\"{:.1}\">{}</text>",
+                px + 6.0,
+                py - 6.0,
+                Self::label(i)
+            );
+        }
+
+        let axis_labels = [
+            (MARGIN, HEIGHT - MARGIN + 16.0, format!("{:.4} FLOP/B", lo)),
+            (
+                WIDTH - MARGIN - 90.0,
+                HEIGHT - MARGIN + 16.0,
+                format!("{:.4} FLOP/B", hi),
+            ),
+            (
+                4.0,
+                HEIGHT - MARGIN,
+                format_scaled(10f64.powf(y0), &FLOP_UNITS),
+            ),
+            (4.0, MARGIN, format_scaled(10f64.powf(y1), &FLOP_UNITS)),
+        ];
+        for (lx, ly, label) in axis_labels {
+            let _ = writeln!(svg, "<text x=\"{lx:.1}\" y=\"{ly:.1}\">{}</text>", label);
+        }
+        for (i, line) in self.legend().lines().enumerate() {
+            let _ = writeln!(
+                svg,
+                "<text x=\"{MARGIN}\" y=\"{:.1}\">{}</text>",
+                HEIGHT - MARGIN + 34.0 + 14.0 * i as f64,
+                escape(line)
+            );
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+op_metadata! {
+    #[derive(derive_new::new)]
+    pub struct PeakMeta {
+        numel: u32,
+        iterations: u32,
+    }
+}
+
+/// Kernels used to measure [`DevicePeaks`].
+#[derive(Debug, Clone, Copy)]
+enum PeakKernel {
+    Bandwidth,
+    Flops,
+}
+
+impl PeakKernel {
+    const WORKGROUP_SIZE: usize = 256;
+    const FMA_ITERATIONS: u32 = 256;
+
+    //Number of vec4s processed
+    fn numel(&self) -> usize {
+        match self {
+            PeakKernel::Bandwidth => 1 << 22,
+            PeakKernel::Flops => 1 << 20,
+        }
+    }
+}
+
+impl KernelBench for PeakKernel {
+    type Metadata = PeakMeta;
+
+    fn name() -> &'static str {
+        "DevicePeak"
+    }
+
+    fn source(&self, workload: &Workload) -> KernelSource {
+        let template = match self {
+            PeakKernel::Bandwidth => wgsl_template!("roofline/bandwidth.wgsl"),
+            PeakKernel::Flops => wgsl_template!("roofline/flops.wgsl"),
+        };
+        let mut context = tera::Context::new();
+        context.insert_workload(workload);
+        KernelSource::render(template, &context)
+    }
+
+    fn tensors(&self) -> Vec<CPUTensor> {
+        let input = CPUTensor::zeros::<f32>(shape![self.numel() * 4]);
+        let output = CPUTensor::zeros::<f32>(shape![self.numel() * 4]);
+        vec![input, output]
+    }
+
+    fn workload(&self, _: &[CPUTensor]) -> Workload {
+        let groups = Workload::ceil(self.numel(), Self::WORKGROUP_SIZE);
+        Workload::new(
+            wgs![Self::WORKGROUP_SIZE as _, 1, 1],
+            wgc![groups as _, 1, 1],
+        )
+    }
+
+    fn metadata(&self, _: &[CPUTensor]) -> Self::Metadata {
+        PeakMeta::new(self.numel() as _, Self::FMA_ITERATIONS)
+    }
+
+    fn validate(&self, _: &GPUHandle, _: &[CPUTensor]) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn cost(&self, tensors: &[CPUTensor]) -> Cost {
+        match self {
+            PeakKernel::Bandwidth => Cost::from_tensors(0, tensors),
+            //4 chains of vec4 FMAs, 2 FLOPs per lane
+            PeakKernel::Flops => Cost::new(
+                self.numel() as u64 * Self::FMA_ITERATIONS as u64 * 4 * 4 * 2,
+                0,
+            ),
+        }
+    }
+
+    fn dispatches(&self) -> Dispatches {
+        Dispatches::Fixed(10)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn roofline_placement() {
+        //100 GB/s, 1 TFLOP/s, ridge at 10 FLOP/B
+        let peaks = DevicePeaks::new(100e9, 1e12, PeakSource::Configured);
+        assert_eq!(peaks.ridge_point(), 10.0);
+
+        //1 FLOP/B moving 1 MB in 20 us: 50 GB/s of an attainable 100 GB/s
+        let memory = RooflinePoint::new("memory".into(), Cost::new(1_000_000, 1_000_000), 20e3);
+        assert_eq!(memory.bound(&peaks), Bound::Memory);
+        assert!((memory.efficiency(&peaks) - 50.0).abs() < 1e-9);
+
+        //100 FLOP/B, 1 GFLOP in 4 ms: 250 GFLOP/s of an attainable 1 TFLOP/s
+        let compute =
+            RooflinePoint::new("compute".into(), Cost::new(1_000_000_000, 10_000_000), 4e6);
+        assert_eq!(compute.bound(&peaks), Bound::Compute);
+        assert!((compute.efficiency(&peaks) - 25.0).abs() < 1e-9);
+
+        let mut roofline = Roofline::new(peaks);
+        roofline.insert(memory.clone());
+        roofline.insert(compute);
+        roofline.insert(memory);
+        assert_eq!(roofline.points.len(), 2);
+        assert!(roofline
+            .to_text()
+            .contains("A: memory 50.0% of attainable roofline"));
+    }
+}
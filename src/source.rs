@@ -0,0 +1,255 @@
+use std::{fmt::Display, ops::Deref};
+
+use naga::{
+    valid::{Capabilities, ModuleInfo, ValidationFlags, Validator},
+    Module,
+};
+
+/// A WGSL template, with the path it was included from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Template {
+    pub path: &'static str,
+    pub text: &'static str,
+}
+
+/// Includes a template from the `kernels/` directory of the calling crate, e.g.
+/// `wgsl_template!("sgemm/gemm_scalar.wgsl")`.
+#[macro_export]
+macro_rules! wgsl_template {
+    ($path:literal) => {
+        $crate::Template {
+            path: concat!("kernels/", $path),
+            text: include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/kernels/", $path)),
+        }
+    };
+}
+
+//Whether a rendered line could have come from a template line, with every `{{ .. }}`
+//standing in for anything. Lines holding only `{% .. %}` tags render to nothing.
+fn renders_to(template_line: &str, rendered: &str) -> bool {
+    let mut pieces = vec![];
+    let mut rest = template_line;
+    while let Some(start) = [rest.find("{{"), rest.find("{%")]
+        .into_iter()
+        .flatten()
+        .min()
+    {
+        pieces.push(&rest[..start]);
+        let close = if rest[start..].starts_with("{{") {
+            "}}"
+        } else {
+            "%}"
+        };
+        match rest[start..].find(close) {
+            Some(end) => rest = &rest[start + end + 2..],
+            None => return false,
+        }
+    }
+    pieces.push(rest);
+    let pieces = pieces
+        .iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>();
+    if pieces.is_empty() {
+        return false;
+    }
+    let mut position = 0;
+    for piece in pieces {
+        match rendered[position..].find(piece) {
+            Some(found) => position += found + piece.len(),
+            None => return false,
+        }
+    }
+    true
+}
+
+/// # KernelSource
+///
+/// WGSL rendered from a [`Template`], keeping the template and its context so that
+/// diagnostics can point back at the `.wgsl` file rather than only the rendered source.
+#[derive(Debug, Clone)]
+pub struct KernelSource {
+    rendered: String,
+    template: Option<Template>,
+    context: Option<tera::Context>,
+}
+
+impl KernelSource {
+    /// Renders `template` with `context`, panicking with the template's path on failure.
+    pub fn render(template: Template, context: &tera::Context) -> Self {
+        let rendered = tera::Tera::one_off(template.text, context, false).unwrap_or_else(|e| {
+            panic!(
+                "Failed to render {}: {:#}",
+                template.path,
+                anyhow::Error::from(e)
+            )
+        });
+        Self {
+            rendered,
+            template: Some(template),
+            context: Some(context.clone()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.rendered
+    }
+
+    pub fn template(&self) -> Option<Template> {
+        self.template
+    }
+
+    pub fn context(&self) -> Option<&tera::Context> {
+        self.context.as_ref()
+    }
+
+    /// Best-effort 1-based line of the template that produced the 1-based `rendered_line`:
+    /// the template line nearest to it whose literal text matches.
+    pub fn template_line(&self, rendered_line: usize) -> Option<usize> {
+        let template = self.template?;
+        let rendered = self.rendered.lines().nth(rendered_line.checked_sub(1)?)?;
+        if rendered.trim().is_empty() {
+            return None;
+        }
+        template
+            .text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| renders_to(line, rendered))
+            .map(|(i, _)| i + 1)
+            .min_by_key(|line| line.abs_diff(rendered_line))
+    }
+
+    /// Parses and validates the rendered source with naga, including the uniformity
+    /// analysis, allowing every capability.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.module().map(|_| ())
+    }
+
+    /// The validated naga module, allowing every capability, e.g. for translation to other
+    /// shading languages or checks without a device.
+    pub fn module(&self) -> anyhow::Result<(Module, ModuleInfo)> {
+        self.module_for(Capabilities::all())
+    }
+
+    /// The naga module, validated with only `capabilities`, e.g. [`crate::GPUHandle::capabilities`]
+    /// so that a shader needing a feature the device lacks is rejected before wgpu sees it.
+    pub fn module_for(&self, capabilities: Capabilities) -> anyhow::Result<(Module, ModuleInfo)> {
+        let source = self.as_str();
+        let path = match self.template {
+            Some(template) => format!("{} (rendered)", template.path),
+            None => "wgsl".to_string(),
+        };
+        let module = naga::front::wgsl::parse_str(source).map_err(|e| {
+            let line = e.location(source).map(|l| l.line_number as usize);
+            self.diagnostic(e.emit_to_string_with_path(source, &path), line)
+        })?;
+        let info = Validator::new(ValidationFlags::all(), capabilities)
+            .validate(&module)
+            .map_err(|e| {
+                let line = e.location(source).map(|l| l.line_number as usize);
+                self.diagnostic(e.emit_to_string_with_path(source, &path), line)
+            })?;
+        Ok((module, info))
+    }
+
+    //Appends the offending lines and the template context to a naga diagnostic
+    fn diagnostic(&self, emitted: String, line: Option<usize>) -> anyhow::Error {
+        let mut message = emitted.trim_end().to_string();
+        if let Some(line) = line {
+            if let Some(rendered) = self.rendered.lines().nth(line - 1) {
+                message += &format!("\n\nrendered line {}: {}", line, rendered.trim());
+            }
+            if let Some(template) = self.template {
+                message += &match self.template_line(line) {
+                    Some(t) => format!(
+                        "\n{}:{}: {}",
+                        template.path,
+                        t,
+                        template.text.lines().nth(t - 1).unwrap_or_default().trim()
+                    ),
+                    None => format!("\n{}: no matching template line", template.path),
+                };
+            }
+        }
+        if let Some(context) = &self.context {
+            if let serde_json::Value::Object(variables) = context.clone().into_json() {
+                let variables = variables
+                    .iter()
+                    .map(|(k, v)| format!("{} = {}", k, v))
+                    .collect::<Vec<_>>();
+                message += &format!("\ncontext: {}", variables.join(", "));
+            }
+        }
+        anyhow::anyhow!(message)
+    }
+}
+
+impl From<String> for KernelSource {
+    fn from(rendered: String) -> Self {
+        Self {
+            rendered,
+            template: None,
+            context: None,
+        }
+    }
+}
+
+impl Deref for KernelSource {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.rendered
+    }
+}
+
+impl Display for KernelSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.rendered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    const TEMPLATE: Template = Template {
+        path: "kernels/test/broken.wgsl",
+        text: "@group(0) @binding(0)
+var<storage, read_write> X: array<f32>;
+
+{% if UNROLL %}
+const UNROLL: u32 = {{ UNROLL }}u;
+{% endif %}
+
+@compute @workgroup_size({{ workgroup_size_x }}, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    X[global_id.x] = {{ VALUE }};
+}
+",
+    };
+
+    #[test]
+    pub fn template_diagnostics() {
+        let mut context = tera::Context::new();
+        context.insert("UNROLL", &4);
+        context.insert("workgroup_size_x", &64);
+        context.insert("VALUE", "1.0");
+        let source = KernelSource::render(TEMPLATE, &context);
+        assert!(source.validate().is_ok());
+        assert_eq!(source.template_line(5), Some(5));
+        assert_eq!(source.template_line(8), Some(8));
+
+        //Rendered without UNROLL, the `{% if %}` block collapses and lines shift
+        context.remove("UNROLL");
+        context.insert("VALUE", "true");
+        let source = KernelSource::render(TEMPLATE, &context);
+        let error = source.validate().unwrap_err().to_string();
+        let rendered_line = source.lines().position(|l| l.contains("= true")).unwrap() + 1;
+        assert_ne!(rendered_line, 10);
+        assert_eq!(source.template_line(rendered_line), Some(10));
+        assert!(error.contains("kernels/test/broken.wgsl:10: X[global_id.x] = {{ VALUE }};"));
+        assert!(error.contains("context: VALUE = \"true\", workgroup_size_x = 64"));
+    }
+}
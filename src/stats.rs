@@ -0,0 +1,190 @@
+/// Returns the `p`th percentile (0-100) of sorted values, interpolating between ranks.
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    assert!(!sorted.is_empty());
+    let rank = (p / 100.0) * (sorted.len() - 1) as f64;
+    let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (rank - lo as f64)
+}
+
+/// Distribution of individually timed dispatches, in nanoseconds.
+#[derive(Debug, Clone)]
+pub struct DispatchStats {
+    pub count: usize,
+    pub mean: f64,
+    pub min: f64,
+    pub median: f64,
+    pub p95: f64,
+    pub first: f64, //Median latency of the first dispatch in a sample
+}
+
+impl DispatchStats {
+    pub fn new(durations: &[f64], first: &[f64]) -> Option<Self> {
+        if durations.is_empty() || first.is_empty() {
+            return None;
+        }
+        let mut sorted = durations.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let mut first = first.to_vec();
+        first.sort_by(f64::total_cmp);
+
+        Some(Self {
+            count: sorted.len(),
+            mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            min: sorted[0],
+            median: percentile(&sorted, 50.0),
+            p95: percentile(&sorted, 95.0),
+            first: percentile(&first, 50.0),
+        })
+    }
+}
+
+impl std::fmt::Display for DispatchStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "mean: {:.4} ns min: {:.4} ns median: {:.4} ns p95: {:.4} ns first: {:.4} ns ({} dispatches)",
+            self.mean, self.min, self.median, self.p95, self.first, self.count
+        )
+    }
+}
+
+//Lanczos approximation of ln Γ(x), for x > 0
+fn ln_gamma(x: f64) -> f64 {
+    const G: f64 = 7.0;
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        //Reflection formula
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + G + 0.5;
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .skip(1)
+        .fold(COEFFICIENTS[0], |acc, (i, c)| acc + c / (x + i as f64));
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+//Continued fraction for the incomplete beta function, evaluated with Lentz's method
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const MAX_ITERATIONS: usize = 200;
+    const EPSILON: f64 = 1e-14;
+    const TINY: f64 = 1e-300;
+    let guard = |v: f64| if v.abs() < TINY { TINY } else { v };
+
+    let mut c = 1.0;
+    let mut d = 1.0 / guard(1.0 - (a + b) * x / (a + 1.0));
+    let mut h = d;
+    for m in 1..=MAX_ITERATIONS {
+        let m = m as f64;
+        let even = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        d = 1.0 / guard(1.0 + even * d);
+        c = guard(1.0 + even / c);
+        h *= d * c;
+        let odd = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0));
+        d = 1.0 / guard(1.0 + odd * d);
+        c = guard(1.0 + odd / c);
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    h
+}
+
+/// Regularized incomplete beta function I_x(a, b).
+pub fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let ln_front = ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln();
+    //The continued fraction converges fastest on the side of the mean
+    if x < (a + 1.0) / (a + b + 2.0) {
+        ln_front.exp() * beta_continued_fraction(a, b, x) / a
+    } else {
+        1.0 - ln_front.exp() * beta_continued_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// Probability that a Student's t variable with `df` degrees of freedom exceeds `t`.
+pub fn student_t_sf(t: f64, df: f64) -> f64 {
+    let tail = 0.5 * incomplete_beta(df / 2.0, 0.5, df / (df + t * t));
+    if t > 0.0 {
+        tail
+    } else {
+        1.0 - tail
+    }
+}
+
+/// Summary of a set of samples, as needed by [`welch_t_test`].
+#[derive(Debug, Clone, Copy, PartialEq, derive_new::new)]
+pub struct SampleSummary {
+    pub n: usize,
+    pub mean: f64,
+    pub stddev: f64, //Sample standard deviation
+}
+
+/// One-sided Welch's t-test of whether `b` has a larger mean than `a`, without assuming
+/// equal variances. Returns the p-value.
+pub fn welch_t_test(a: SampleSummary, b: SampleSummary) -> f64 {
+    if a.n < 2 || b.n < 2 {
+        return f64::NAN;
+    }
+    let va = a.stddev.powi(2) / a.n as f64;
+    let vb = b.stddev.powi(2) / b.n as f64;
+    let difference = b.mean - a.mean;
+    if va + vb == 0.0 {
+        //Noiseless samples, any difference is certain
+        return if difference > 0.0 { 0.0 } else { 1.0 };
+    }
+    let t = difference / (va + vb).sqrt();
+    let df = (va + vb).powi(2) / (va.powi(2) / (a.n - 1) as f64 + vb.powi(2) / (b.n - 1) as f64);
+    student_t_sf(t, df)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn dispatch_stats() {
+        let durations = (1..=100).map(|d| d as f64).collect::<Vec<_>>();
+        let stats = DispatchStats::new(&durations, &[90.0, 100.0, 80.0]).unwrap();
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.mean, 50.5);
+        assert_eq!(stats.median, 50.5);
+        assert!((stats.p95 - 95.05).abs() < 1e-9);
+        assert_eq!(stats.first, 90.0);
+    }
+
+    #[test]
+    pub fn welch() {
+        //Reference values from scipy.stats.t.sf
+        assert!((student_t_sf(2.0, 10.0) - 0.036_694).abs() < 1e-6);
+        assert!((student_t_sf(-1.0, 4.0) - 0.813_050).abs() < 1e-6);
+        assert!((student_t_sf(0.0, 7.0) - 0.5).abs() < 1e-12);
+
+        let a = SampleSummary::new(10, 100.0, 2.0);
+        let slower = SampleSummary::new(10, 104.0, 2.0);
+        let noisy = SampleSummary::new(10, 104.0, 20.0);
+        assert!(welch_t_test(a, slower) < 1e-3);
+        assert!(welch_t_test(a, noisy) > 0.2);
+        assert!(welch_t_test(slower, a) > 0.99);
+    }
+}
@@ -49,25 +49,7 @@ impl Drop for CPUStorage {
 impl Storage for CPUStorage {
     //No allocations are pooled here because we don't care
     fn to_gpu(self, handle: &GPUHandle) -> GPUStorage {
-        let mut min_bytes = [0; 16];
-        let bytes = if self.as_bytes().len() < 16 {
-            min_bytes[..self.as_bytes().len()].copy_from_slice(self.as_bytes());
-            &min_bytes //&[u8]
-        } else {
-            self.as_bytes() //&[u8]
-        };
-
-        let buffer = handle
-            .device()
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: None,
-                contents: bytes,
-                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
-            });
-        //These should be batched up
-        handle.queue().submit(None);
-        handle.device().poll(wgpu::Maintain::Wait);
-        GPUStorage(buffer.into())
+        GPUStorage::from_bytes(handle, self.as_bytes())
     }
 
     fn to_cpu(self) -> CPUStorage {
@@ -126,6 +108,29 @@ impl GPUStorage {
         Self(buffer)
     }
 
+    /// Uploads `bytes` into a new storage buffer, padded to the 16 bytes a binding needs.
+    pub fn from_bytes(handle: &GPUHandle, bytes: &[u8]) -> Self {
+        let mut min_bytes = [0; 16];
+        let bytes = if bytes.len() < 16 {
+            min_bytes[..bytes.len()].copy_from_slice(bytes);
+            &min_bytes //&[u8]
+        } else {
+            bytes
+        };
+
+        let buffer = handle
+            .device()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: bytes,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+            });
+        //These should be batched up
+        handle.queue().submit(None);
+        handle.device().poll(wgpu::Maintain::Wait);
+        Self(buffer.into())
+    }
+
     pub fn inner(&self) -> &GPUBuffer {
         &self.0
     }
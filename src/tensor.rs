@@ -1,5 +1,4 @@
 use bytemuck::NoUninit;
-use ndarray::Dimension;
 use numpy::ndarray::{ArrayD, ArrayViewD};
 use rand::{distributions::uniform::SampleUniform, prelude::SeedableRng, rngs::SmallRng};
 use rand_distr::{Distribution, Poisson, StandardNormal};
@@ -113,10 +112,17 @@ impl CPUTensor {
     }
 
     pub fn randn<T: num_traits::Float + DataType + SampleUniform>(shape: Shape) -> Self {
-        let mut rng = SmallRng::from_entropy();
+        Self::randn_from::<T>(shape, &mut SmallRng::from_entropy())
+    }
+
+    /// Samples from `rng`, so that a seeded generator reproduces the same tensors.
+    pub fn randn_from<T: num_traits::Float + DataType + SampleUniform>(
+        shape: Shape,
+        rng: &mut impl rand::Rng,
+    ) -> Self {
         let data = (0..shape.numel())
             .map(|_| {
-                let sample: f32 = StandardNormal.sample(&mut rng);
+                let sample: f32 = StandardNormal.sample(rng);
                 T::from(sample).expect("Failed to convert sample")
             })
             .collect::<Vec<_>>();
@@ -162,59 +168,14 @@ impl CPUTensor {
         format!("{:?}", unsafe { self.to_array_view_unchecked::<f32>() })
     }
 
+    /// Fails with the rendered [`ErrorReport`](crate::ErrorReport) unless every element is close.
     pub fn all_close(&self, other: &Self, atol: f32, rtol: f32) -> anyhow::Result<()> {
-        if self.shape() != other.shape() {
-            anyhow::bail!("Shape mismatch {:?} != {:?}", self.shape(), other.shape())
-        }
-        let ma = unsafe { self.to_array_view_unchecked::<f32>() };
-        let mb = unsafe { other.to_array_view_unchecked::<f32>() };
-        let mut elem_cnt = 0;
-        let mut fail_cnt = 0;
-        let mut total_error = 0f32;
-        let mut mae = -1f32;
-        let mut mae_idxs = Default::default();
-        ndarray::indices_of(&ma).into_iter().try_for_each(|idxs| {
-            let (a, b) = (ma[&idxs], mb[&idxs]);
-            let abs_diff = (a - b).abs();
-            let cur_mae = mae.max(abs_diff);
-            if cur_mae > mae {
-                mae = cur_mae;
-                mae_idxs = idxs.clone();
-            }
-            total_error += abs_diff;
-            elem_cnt += 1;
-
-            if !((a.is_nan() && b.is_nan())
-                || (a.is_infinite() && b.is_infinite() && a.signum() == b.signum())
-                || abs_diff <= atol + rtol * b.abs())
-            {
-                let slice = idxs.slice();
-                log::trace!(
-                    "Mismatch at {:?}: {:?} != {:?} (atol={}, rtol={})",
-                    slice,
-                    a,
-                    b,
-                    atol,
-                    rtol
-                );
-                fail_cnt += 1;
-            }
-            Ok::<(), anyhow::Error>(())
-        })?;
-        let avg_error = total_error / elem_cnt as f32;
-        let slice = mae_idxs.slice();
-        if fail_cnt > 0 {
-            anyhow::bail!(
-                "{} samples not close - AVGE={} MAE={} at {:?}",
-                fail_cnt,
-                avg_error,
-                mae,
-                slice,
-            );
-        } else {
-            println!("All close - AVGE={} MAE={} at {:?}", avg_error, mae, slice,);
-            Ok(())
+        let report = self.error_report(other, atol, rtol)?;
+        if !report.is_close() {
+            anyhow::bail!("{}", report);
         }
+        log::debug!("All close - {}", report.summary());
+        Ok(())
     }
 }
 
@@ -0,0 +1,92 @@
+use criterion::Throughput;
+
+use crate::{CPUTensor, Storage};
+
+pub const BYTE_UNITS: [&str; 5] = ["B/s", "KB/s", "MB/s", "GB/s", "TB/s"];
+pub const FLOP_UNITS: [&str; 5] = ["FLOP/s", "KFLOP/s", "MFLOP/s", "GFLOP/s", "TFLOP/s"];
+
+/// The work performed by a single dispatch of a kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, derive_new::new)]
+pub struct Cost {
+    pub flops: u64,
+    pub bytes: u64, //Bytes moved between the kernel and global memory
+}
+
+impl Cost {
+    /// Assumes every tensor is read or written exactly once.
+    pub fn from_tensors(flops: u64, tensors: &[CPUTensor]) -> Self {
+        let bytes = tensors.iter().map(|t| t.storage().n_bytes() as u64).sum();
+        Self { flops, bytes }
+    }
+
+    /// FLOPs performed per byte moved.
+    pub fn arithmetic_intensity(&self) -> f64 {
+        self.flops as f64 / self.bytes as f64
+    }
+
+    /// Formats the rates achieved when a dispatch takes `ns` nanoseconds.
+    pub fn format_rates(&self, ns: f64) -> String {
+        let per_second = |quantity: u64| quantity as f64 * 1e9 / ns;
+        let mut rates = vec![];
+        if self.flops > 0 {
+            rates.push(format_scaled(per_second(self.flops), &FLOP_UNITS));
+        }
+        if self.bytes > 0 {
+            rates.push(format_scaled(per_second(self.bytes), &BYTE_UNITS));
+        }
+        if self.flops > 0 && self.bytes > 0 {
+            rates.push(format!("AI {:.4} FLOP/B", self.arithmetic_intensity()));
+        }
+        rates.join(" | ")
+    }
+}
+
+impl From<&Throughput> for Cost {
+    fn from(throughput: &Throughput) -> Self {
+        match throughput {
+            Throughput::Bytes(b) | Throughput::BytesDecimal(b) => Cost::new(0, *b),
+            Throughput::Elements(e) => Cost::new(*e, 0),
+        }
+    }
+}
+
+/// Picks the largest SI prefix (up to T) that keeps `value` at or above 1.
+/// Returns the denominator and the matching unit.
+pub fn scale_si(value: f64, units: &[&'static str; 5]) -> (f64, &'static str) {
+    let mut denominator = 1.0;
+    for unit in units.iter().take(units.len() - 1) {
+        if value < denominator * 1000.0 {
+            return (denominator, unit);
+        }
+        denominator *= 1000.0;
+    }
+    (denominator, units[units.len() - 1])
+}
+
+pub fn format_scaled(value: f64, units: &[&'static str; 5]) -> String {
+    let (denominator, unit) = scale_si(value, units);
+    format!("{:.4} {}", value / denominator, unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn si_scaling() {
+        assert_eq!(scale_si(999.0, &BYTE_UNITS), (1.0, "B/s"));
+        assert_eq!(scale_si(1.5e3, &BYTE_UNITS), (1e3, "KB/s"));
+        assert_eq!(scale_si(2.0e9, &FLOP_UNITS), (1e9, "GFLOP/s"));
+        assert_eq!(scale_si(3.0e15, &FLOP_UNITS), (1e12, "TFLOP/s"));
+    }
+
+    #[test]
+    pub fn cost_rates() {
+        let cost = Cost::new(2_000_000, 1_000_000);
+        assert_eq!(cost.arithmetic_intensity(), 2.0);
+        assert_eq!(
+            cost.format_rates(1e3),
+            "2.0000 TFLOP/s | 1.0000 TB/s | AI 2.0000 FLOP/B"
+        );
+    }
+}
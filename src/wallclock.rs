@@ -0,0 +1,71 @@
+use std::time::Instant;
+
+use criterion::measurement::{Measurement, ValueFormatter};
+
+use crate::WgpuTimerFormatter;
+
+/// The clock used to time a benchmark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    /// GPU timestamps written at compute pass boundaries.
+    Timestamp,
+    /// Host time around queue submission and `device.poll(Wait)`.
+    WallClock,
+}
+
+impl std::fmt::Display for Clock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Clock::Timestamp => write!(f, "GPU timestamp"),
+            Clock::WallClock => write!(f, "wall-clock"),
+        }
+    }
+}
+
+/// # WallClockTimer
+///
+/// Fallback measurement for adapters without `TIMESTAMP_QUERY`.
+/// Each iteration of `dispatch` submits and waits on the device, so timing the
+/// iterations on the host captures the GPU work plus submission overhead.
+#[derive(Debug)]
+pub struct WallClockTimer {
+    formatter: WgpuTimerFormatter,
+}
+
+impl Default for WallClockTimer {
+    fn default() -> Self {
+        Self {
+            formatter: WgpuTimerFormatter::new(Clock::WallClock),
+        }
+    }
+}
+
+impl Measurement for &WallClockTimer {
+    type Intermediate = Instant;
+
+    type Value = u64; // Nanoseconds
+
+    fn start(&self) -> Self::Intermediate {
+        Instant::now()
+    }
+
+    fn end(&self, start: Self::Intermediate) -> Self::Value {
+        start.elapsed().as_nanos() as u64
+    }
+
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        v1 + v2
+    }
+
+    fn zero(&self) -> Self::Value {
+        0
+    }
+
+    fn to_f64(&self, value: &Self::Value) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &self.formatter
+    }
+}
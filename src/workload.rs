@@ -50,6 +50,45 @@ impl Workload {
     pub fn size(&self) -> &WorkgroupSize {
         &self.size
     }
+
+    /// Checks the workload against the compute limits of a device.
+    pub fn check_limits(&self, limits: &wgpu::Limits) -> anyhow::Result<()> {
+        let WorkgroupSize(x, y, z) = self.size;
+        let max_size = [
+            limits.max_compute_workgroup_size_x,
+            limits.max_compute_workgroup_size_y,
+            limits.max_compute_workgroup_size_z,
+        ];
+        for (dim, (size, max)) in ["x", "y", "z"]
+            .iter()
+            .zip([x, y, z].into_iter().zip(max_size))
+        {
+            anyhow::ensure!(
+                size > 0 && size <= max,
+                "Workgroup size {} = {} outside 1..={}",
+                dim,
+                size,
+                max
+            );
+        }
+        anyhow::ensure!(
+            self.size.total() <= limits.max_compute_invocations_per_workgroup,
+            "{} invocations per workgroup exceeds the limit of {}",
+            self.size.total(),
+            limits.max_compute_invocations_per_workgroup
+        );
+        let (cx, cy, cz) = self.count.as_tuple();
+        for (dim, count) in ["x", "y", "z"].iter().zip([cx, cy, cz]) {
+            anyhow::ensure!(
+                count <= limits.max_compute_workgroups_per_dimension,
+                "Workgroup count {} = {} exceeds the limit of {}",
+                dim,
+                count,
+                limits.max_compute_workgroups_per_dimension
+            );
+        }
+        Ok(())
+    }
 }
 
 ///Used to determine which limit applies